@@ -0,0 +1,18 @@
+#![no_main]
+// The loaders live in a binary crate, include the modules directly.
+#![allow(dead_code)]
+
+use libfuzzer_sys::fuzz_target;
+
+#[path = "../../src/basisu.rs"]
+mod basisu;
+#[path = "../../src/ktx.rs"]
+mod ktx;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(image) = ktx::Image::new(data) {
+        for level in 0..image.levels.len() {
+            let _ = basisu::decode_rgba8(&image, level);
+        }
+    }
+});
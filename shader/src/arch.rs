@@ -0,0 +1,36 @@
+use glace::f32x4;
+use spirv_std::{Image2d, SampledImage};
+
+#[spirv_std_macros::gpu_only]
+pub fn kill() -> ! {
+    unsafe { asm!("OpKill", options(noreturn)) }
+}
+
+/// Load a single texel of the base level without filtering.
+#[spirv_std_macros::gpu_only]
+pub fn texel_fetch(image: &SampledImage<Image2d>, x: i32, y: i32) -> f32x4 {
+    unsafe {
+        let mut result = f32x4::default();
+        let lod = 0i32;
+        asm!(
+            "%f32 = OpTypeFloat 32",
+            "%i32 = OpTypeInt 32 1",
+            "%ivec2 = OpTypeVector %i32 2",
+            "%image_ty = OpTypeImage %f32 2D 0 0 0 1 Unknown",
+            "%sampledImage = OpLoad typeof*{1} {1}",
+            "%image = OpImage %image_ty %sampledImage",
+            "%x = OpLoad %i32 {2}",
+            "%y = OpLoad %i32 {3}",
+            "%coord = OpCompositeConstruct %ivec2 %x %y",
+            "%lod = OpLoad %i32 {4}",
+            "%result = OpImageFetch typeof*{0} %image %coord Lod %lod",
+            "OpStore {0} %result",
+            in(reg) &mut result,
+            in(reg) image,
+            in(reg) &x,
+            in(reg) &y,
+            in(reg) &lod,
+        );
+        result
+    }
+}
@@ -2,10 +2,13 @@
 #![feature(lang_items, register_attr, asm)]
 #![register_attr(spirv)]
 
+use crate::arch::{kill, texel_fetch};
 use crate::cubemap::{cubemap_sample, cubemap_sample_lod};
+use core::f32::consts::PI;
 use glace::{f32x2, f32x3, f32x3x3, f32x4, f32x4x4, vec2, vec3, vec4};
 use spirv_std::{Cubemap, Image2d, SampledImage};
 
+mod arch;
 mod cubemap;
 
 #[spirv(block)]
@@ -18,18 +21,210 @@ pub struct LocalsPbr {
     specular_mipmaps: u32,
 }
 
+#[spirv(block)]
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct LocalsMesh {
+    model_to_world: f32x4x4,
+    normal_to_world: f32x4x4,
+    skinned: u32,
+    morph_targets: u32,
+    _pad: [u32; 2],
+    morph_weights: [f32x4; MAX_MORPH_TARGETS / 4],
+}
+
+const MAX_MORPH_TARGETS: usize = 64;
+const MORPH_TEXTURE_WIDTH: u32 = 4096;
+
+const MAX_JOINTS: usize = 128;
+
+#[spirv(block)]
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct LocalsSkin {
+    joints: [f32x4x4; MAX_JOINTS],
+}
+
+/// First two rows of the 3x3 texture coordinate transformation.
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct UvTransform {
+    x: f32x4,
+    y: f32x4,
+}
+
+#[spirv(block)]
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct LocalsMaterial {
+    base_color_factor: f32x4,
+    emissive_factor: f32x4,
+    base_color_transform: UvTransform,
+    normal_transform: UvTransform,
+    metallic_roughness_transform: UvTransform,
+    occlusion_transform: UvTransform,
+    emissive_transform: UvTransform,
+    metallic_factor: f32,
+    roughness_factor: f32,
+    normal_scale: f32,
+    occlusion_strength: f32,
+    alpha_cutoff: f32,
+    alpha_mode: u32,
+    base_color_tex_coord: u32,
+    normal_tex_coord: u32,
+    metallic_roughness_tex_coord: u32,
+    occlusion_tex_coord: u32,
+    emissive_tex_coord: u32,
+}
+
+const MAX_LIGHTS: usize = 16;
+
+const LIGHT_DIRECTIONAL: f32 = 0.0;
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct Light {
+    position: f32x4,
+    direction: f32x4,
+    color: f32x4,
+    spot: f32x4,
+}
+
+#[spirv(block)]
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct LocalsLights {
+    num_lights: u32,
+    _pad: [u32; 3],
+    lights: [Light; MAX_LIGHTS],
+}
+
+const ALPHA_MODE_MASK: u32 = 1;
+const ALPHA_MODE_BLEND: u32 = 2;
+
 fn mix(a: f32, b: f32, t: f32) -> f32 {
     a + (b - a) * t
 }
 
+/// Select the UV set of a material texture and apply its transformation.
+fn tex_coord(set: u32, transform: UvTransform, texcoord_0: f32x2, texcoord_1: f32x2) -> f32x2 {
+    let uv = if set == 1 { texcoord_1 } else { texcoord_0 };
+    vec2(
+        transform.x.x * uv.x + transform.x.y * uv.y + transform.x.z,
+        transform.y.x * uv.x + transform.y.y * uv.y + transform.y.z,
+    )
+}
+
+fn component(v: f32x4, i: u32) -> f32 {
+    match i {
+        0 => v.x,
+        1 => v.y,
+        2 => v.z,
+        _ => v.w,
+    }
+}
+
+fn morph_delta(u_morph_targets: &SampledImage<Image2d>, texel: u32) -> f32x4 {
+    texel_fetch(
+        u_morph_targets,
+        (texel % MORPH_TEXTURE_WIDTH) as i32,
+        (texel / MORPH_TEXTURE_WIDTH) as i32,
+    )
+}
+
+/// Radiance reflected towards the viewer from a single punctual light.
+///
+/// Uses the same Lambert diffuse and GGX specular terms as the prefiltered environment.
+fn punctual_light(
+    light: Light,
+    position_world: f32x3,
+    normal_world: f32x3,
+    view_world: f32x3,
+    diffuse_color: f32x3,
+    specular_color: f32x3,
+    roughness: f32,
+) -> f32x3 {
+    let light_dir = vec3(light.direction.x, light.direction.y, light.direction.z);
+    let (light_world, attenuation) = if light.position.w == LIGHT_DIRECTIONAL {
+        (vec3(-light_dir.x, -light_dir.y, -light_dir.z), 1.0)
+    } else {
+        let to_light = vec3(light.position.x, light.position.y, light.position.z) - position_world;
+        let distance_sq = to_light.dot(to_light).max(0.0001);
+        let light_world = to_light.normalize();
+        // Smooth range falloff as recommended by `KHR_lights_punctual`.
+        let ratio = distance_sq * light.direction.w;
+        let range = (1.0 - ratio * ratio).max(0.0).min(1.0);
+        // Spot cone attenuation, point lights use `scale = 0` and `offset = 1`.
+        let cos_angle = -light_dir.dot(light_world);
+        let cone = (cos_angle * light.spot.x + light.spot.y).max(0.0).min(1.0);
+        (light_world, range / distance_sq * cone * cone)
+    };
+
+    let n_dot_l = normal_world.dot(light_world);
+    let n_dot_v = normal_world.dot(view_world).max(0.0001);
+    if n_dot_l <= 0.0 || attenuation <= 0.0 {
+        return vec3(0.0, 0.0, 0.0);
+    }
+
+    let half = (light_world + view_world).normalize();
+    let n_dot_h = normal_world.dot(half).max(0.0);
+    let v_dot_h = view_world.dot(half).max(0.0);
+
+    let alpha = roughness * roughness;
+    let alpha_sq = alpha * alpha;
+
+    // Schlick Fresnel
+    let t = 1.0 - v_dot_h;
+    let fresnel_weight = t * t * t * t * t;
+    let fresnel = vec3(
+        mix(specular_color.x, 1.0, fresnel_weight),
+        mix(specular_color.y, 1.0, fresnel_weight),
+        mix(specular_color.z, 1.0, fresnel_weight),
+    );
+
+    // GGX distribution and height-correlated Smith visibility.
+    let d = n_dot_h * n_dot_h * (alpha_sq - 1.0) + 1.0;
+    let distribution = alpha_sq / (PI * d * d);
+    let visibility = 0.5
+        / (n_dot_l * (n_dot_v * (1.0 - alpha) + alpha)
+            + n_dot_v * (n_dot_l * (1.0 - alpha) + alpha));
+    let specular = distribution * visibility;
+
+    let radiance = attenuation * n_dot_l;
+    vec3(
+        light.color.x
+            * radiance
+            * ((1.0 - fresnel.x) * diffuse_color.x / PI + fresnel.x * specular),
+        light.color.y
+            * radiance
+            * ((1.0 - fresnel.y) * diffuse_color.y / PI + fresnel.y * specular),
+        light.color.z
+            * radiance
+            * ((1.0 - fresnel.z) * diffuse_color.z / PI + fresnel.z * specular),
+    )
+}
+
+/// Blend the vector transformed by the weighted joint matrices.
+fn skin(v: f32x4, joints: f32x4, weights: f32x4, skin: &LocalsSkin) -> f32x4 {
+    weights.x * (v * skin.joints[joints.x as usize])
+        + weights.y * (v * skin.joints[joints.y as usize])
+        + weights.z * (v * skin.joints[joints.z as usize])
+        + weights.w * (v * skin.joints[joints.w as usize])
+}
+
 #[spirv(fragment)]
 pub fn main_fs(
     f_normal_world: f32x3,
     f_texcoord: f32x2,
     f_tangent_world: f32x4,
     f_position_world: f32x3,
+    f_color: f32x4,
+    f_texcoord_1: f32x2,
+    #[spirv(front_facing)] f_front_facing: bool,
     output: &mut f32x4,
     #[spirv(uniform, binding = 0)] u_locals_fs: &LocalsPbr,
+    #[spirv(uniform, binding = 2)] u_material_fs: &LocalsMaterial,
+    #[spirv(uniform, binding = 4)] u_lights_fs: &LocalsLights,
     #[spirv(uniform_constant, binding = 0)] u_albedo: &SampledImage<Image2d>,
     #[spirv(uniform_constant, binding = 1)] u_normals: &SampledImage<Image2d>,
     #[spirv(uniform_constant, binding = 2)] u_metal_roughness: &SampledImage<Image2d>,
@@ -37,16 +232,29 @@ pub fn main_fs(
     #[spirv(uniform_constant, binding = 4)] u_diffuse_map: &SampledImage<Cubemap>,
     #[spirv(uniform_constant, binding = 5)] u_specular_map: &SampledImage<Cubemap>,
     #[spirv(uniform_constant, binding = 6)] u_lut_ggx: &SampledImage<Image2d>,
+    #[spirv(uniform_constant, binding = 7)] u_emissive: &SampledImage<Image2d>,
 ) {
-    let normal = vec3(f_normal_world.x, f_normal_world.y, f_normal_world.z).normalize();
-    let tangent = vec3(f_tangent_world.x, f_tangent_world.y, f_tangent_world.z).normalize();
-    let bitangent = normal.cross(tangent).normalize();
+    // Back faces of double-sided materials are shaded with the flipped tangent frame.
+    let facing = if f_front_facing { 1.0 } else { -1.0 };
+    let normal = facing * vec3(f_normal_world.x, f_normal_world.y, f_normal_world.z).normalize();
+    let tangent =
+        facing * vec3(f_tangent_world.x, f_tangent_world.y, f_tangent_world.z).normalize();
+    // Tangent `w` holds the handedness of the tangent frame.
+    let handedness = if f_tangent_world.w < 0.0 { -1.0 } else { 1.0 };
+    let bitangent = facing * handedness * normal.cross(tangent).normalize();
+
+    let material = u_material_fs;
 
     let u_normals = u_normals;
-    let normal_tangent: f32x4 = u_normals.sample(f_texcoord);
+    let normal_tangent: f32x4 = u_normals.sample(tex_coord(
+        material.normal_tex_coord,
+        material.normal_transform,
+        f_texcoord,
+        f_texcoord_1,
+    ));
     let normal_tangent = vec3(
-        2.0 * normal_tangent.x - 1.0,
-        2.0 * normal_tangent.y - 1.0,
+        (2.0 * normal_tangent.x - 1.0) * material.normal_scale,
+        (2.0 * normal_tangent.y - 1.0) * material.normal_scale,
         2.0 * normal_tangent.z - 1.0,
     )
     .normalize();
@@ -58,7 +266,22 @@ pub fn main_fs(
     let normal_world = (normal_tangent * tangent_to_world).normalize();
 
     // Diffuse IBL
-    let albedo: f32x4 = u_albedo.sample(f_texcoord);
+    let base_color: f32x4 = u_albedo.sample(tex_coord(
+        material.base_color_tex_coord,
+        material.base_color_transform,
+        f_texcoord,
+        f_texcoord_1,
+    ));
+    let albedo = vec4(
+        base_color.x * material.base_color_factor.x * f_color.x,
+        base_color.y * material.base_color_factor.y * f_color.y,
+        base_color.z * material.base_color_factor.z * f_color.z,
+        base_color.w * material.base_color_factor.w * f_color.w,
+    );
+
+    if material.alpha_mode == ALPHA_MODE_MASK && albedo.w < material.alpha_cutoff {
+        kill();
+    }
 
     let irradiance: f32x4 = cubemap_sample(u_diffuse_map, normal_world);
     let light_diffuse = vec3(
@@ -68,10 +291,15 @@ pub fn main_fs(
     );
 
     // Specular IBL
-    let metal_roughness: f32x4 = u_metal_roughness.sample(f_texcoord);
+    let metal_roughness: f32x4 = u_metal_roughness.sample(tex_coord(
+        material.metallic_roughness_tex_coord,
+        material.metallic_roughness_transform,
+        f_texcoord,
+        f_texcoord_1,
+    ));
 
-    let metalness = metal_roughness.z;
-    let roughness = metal_roughness.y;
+    let metalness = metal_roughness.z * material.metallic_factor;
+    let roughness = metal_roughness.y * material.roughness_factor;
 
     let specular_color = vec3(
         mix(0.04, albedo.x, metalness),
@@ -101,15 +329,62 @@ pub fn main_fs(
     );
 
     // AO
-    let ambient_occlusion: f32x4 = u_ambient_occlusion.sample(f_texcoord);
+    let occlusion: f32x4 = u_ambient_occlusion.sample(tex_coord(
+        material.occlusion_tex_coord,
+        material.occlusion_transform,
+        f_texcoord,
+        f_texcoord_1,
+    ));
+    let ambient_occlusion = 1.0 + material.occlusion_strength * (occlusion.x - 1.0);
+
+    // Punctual lights
+    let diffuse_color = vec3(
+        albedo.x * (1.0 - metalness),
+        albedo.y * (1.0 - metalness),
+        albedo.z * (1.0 - metalness),
+    );
+    let mut light_punctual = vec3(0.0, 0.0, 0.0);
+    let mut i = 0;
+    while i < u_lights_fs.num_lights {
+        light_punctual = light_punctual
+            + punctual_light(
+                u_lights_fs.lights[i as usize],
+                f_position_world,
+                normal_world,
+                view_world,
+                diffuse_color,
+                specular_color,
+                roughness,
+            );
+        i += 1;
+    }
+
+    // Emission
+    let emissive: f32x4 = u_emissive.sample(tex_coord(
+        material.emissive_tex_coord,
+        material.emissive_transform,
+        f_texcoord,
+        f_texcoord_1,
+    ));
+    let emission = vec3(
+        emissive.x * material.emissive_factor.x,
+        emissive.y * material.emissive_factor.y,
+        emissive.z * material.emissive_factor.z,
+    );
 
     let color = vec3(
-        (light_diffuse.x + light_specular.x) * ambient_occlusion.x,
-        (light_diffuse.y + light_specular.y) * ambient_occlusion.y,
-        (light_diffuse.z + light_specular.z) * ambient_occlusion.z,
+        (light_diffuse.x + light_specular.x) * ambient_occlusion + light_punctual.x + emission.x,
+        (light_diffuse.y + light_specular.y) * ambient_occlusion + light_punctual.y + emission.y,
+        (light_diffuse.z + light_specular.z) * ambient_occlusion + light_punctual.z + emission.z,
     );
 
-    *output = vec4(color.x, color.y, color.z, 1.0);
+    let alpha = if material.alpha_mode == ALPHA_MODE_BLEND {
+        albedo.w
+    } else {
+        1.0
+    };
+
+    *output = vec4(color.x, color.y, color.z, alpha);
 }
 
 #[spirv(vertex)]
@@ -118,21 +393,62 @@ pub fn main_vs(
     v_normal_obj: f32x3,
     v_texcoord: f32x2,
     v_tangent_obj: f32x4,
+    v_color: f32x4,
+    v_texcoord_1: f32x2,
+    v_joints: f32x4,
+    v_weights: f32x4,
+    #[spirv(vertex_id)] vert_id: i32,
     #[spirv(position)] a_position: &mut f32x4,
     a_normal_world: &mut f32x3,
     a_texcoord: &mut f32x2,
     a_tangent_world: &mut f32x4,
     a_position_world: &mut f32x3,
+    a_color: &mut f32x4,
+    a_texcoord_1: &mut f32x2,
     #[spirv(uniform, binding = 0)] u_locals_vs: &LocalsPbr,
+    #[spirv(uniform, binding = 1)] u_mesh_vs: &LocalsMesh,
+    #[spirv(uniform, binding = 3)] u_skin_vs: &LocalsSkin,
+    #[spirv(uniform_constant, binding = 8)] u_morph_targets: &SampledImage<Image2d>,
 ) {
-    *a_normal_world = v_normal_obj;
+    let mesh = u_mesh_vs;
+
+    let mut pos_obj = vec4(v_position_obj.x, v_position_obj.y, v_position_obj.z, 1.0);
+    let mut normal_obj = vec4(v_normal_obj.x, v_normal_obj.y, v_normal_obj.z, 0.0);
+    let mut tangent_obj = vec4(v_tangent_obj.x, v_tangent_obj.y, v_tangent_obj.z, 0.0);
+
+    // Morph targets store position, normal and tangent deltas for each vertex.
+    let mut target = 0;
+    while target < mesh.morph_targets {
+        let weight = component(mesh.morph_weights[(target / 4) as usize], target % 4);
+        let texel = 3 * (vert_id as u32 * mesh.morph_targets + target);
+        pos_obj = pos_obj + weight * morph_delta(u_morph_targets, texel);
+        normal_obj = normal_obj + weight * morph_delta(u_morph_targets, texel + 1);
+        tangent_obj = tangent_obj + weight * morph_delta(u_morph_targets, texel + 2);
+        target += 1;
+    }
+
+    if mesh.skinned != 0 {
+        pos_obj = skin(pos_obj, v_joints, v_weights, u_skin_vs);
+        normal_obj = skin(normal_obj, v_joints, v_weights, u_skin_vs);
+        tangent_obj = skin(tangent_obj, v_joints, v_weights, u_skin_vs);
+    }
+
+    let normal_world = normal_obj * mesh.normal_to_world;
+    let tangent_world = tangent_obj * mesh.model_to_world;
+    *a_normal_world = vec3(normal_world.x, normal_world.y, normal_world.z);
     *a_texcoord = v_texcoord;
-    *a_tangent_world = v_tangent_obj;
+    *a_color = v_color;
+    *a_texcoord_1 = v_texcoord_1;
+    *a_tangent_world = vec4(
+        tangent_world.x,
+        tangent_world.y,
+        tangent_world.z,
+        v_tangent_obj.w,
+    );
 
     let locals = u_locals_vs;
 
-    let pos_obj = v_position_obj;
-    let pos_world = vec4(pos_obj.x, pos_obj.y, pos_obj.z, 1.0);
+    let pos_world = pos_obj * mesh.model_to_world;
     *a_position_world = vec3(pos_world.x, pos_world.y, pos_world.z);
 
     let pos_view = pos_world * locals.world_to_view;
@@ -0,0 +1,332 @@
+use crate::gltf::{Accessor, AccessorType, ComponentType, Gltf, Sparse};
+use byteorder::{ByteOrder, LittleEndian};
+
+/// Vertex attribute format for reading the accessor as float shader input.
+///
+/// Integer components are either normalized or converted (scaled) to float.
+pub fn vertex_format(accessor: &Accessor) -> anyhow::Result<grr::VertexFormat> {
+    use grr::VertexFormat::*;
+
+    let formats = match (accessor.component_type, accessor.normalized) {
+        (ComponentType::F32, _) => [X32Float, Xy32Float, Xyz32Float, Xyzw32Float],
+        (ComponentType::I8, true) => [X8Inorm, Xy8Inorm, Xyz8Inorm, Xyzw8Inorm],
+        (ComponentType::I8, false) => [X8Iscaled, Xy8Iscaled, Xyz8Iscaled, Xyzw8Iscaled],
+        (ComponentType::U8, true) => [X8Unorm, Xy8Unorm, Xyz8Unorm, Xyzw8Unorm],
+        (ComponentType::U8, false) => [X8Uscaled, Xy8Uscaled, Xyz8Uscaled, Xyzw8Uscaled],
+        (ComponentType::I16, true) => [X16Inorm, Xy16Inorm, Xyz16Inorm, Xyzw16Inorm],
+        (ComponentType::I16, false) => [X16Iscaled, Xy16Iscaled, Xyz16Iscaled, Xyzw16Iscaled],
+        (ComponentType::U16, true) => [X16Unorm, Xy16Unorm, Xyz16Unorm, Xyzw16Unorm],
+        (ComponentType::U16, false) => [X16Uscaled, Xy16Uscaled, Xyz16Uscaled, Xyzw16Uscaled],
+        (ComponentType::U32, false) => [X32Uscaled, Xy32Uscaled, Xyz32Uscaled, Xyzw32Uscaled],
+        (ComponentType::U32, true) => anyhow::bail!("normalized u32 accessors are invalid"),
+    };
+
+    match accessor.ty {
+        AccessorType::Scalar => Ok(formats[0]),
+        AccessorType::Vec2 => Ok(formats[1]),
+        AccessorType::Vec3 => Ok(formats[2]),
+        AccessorType::Vec4 => Ok(formats[3]),
+        ty => anyhow::bail!("{:?} accessors can't be used as vertex attribute", ty),
+    }
+}
+
+pub fn index_ty(accessor: &Accessor) -> anyhow::Result<grr::IndexTy> {
+    anyhow::ensure!(
+        accessor.ty == AccessorType::Scalar,
+        "index accessors must be scalar"
+    );
+    match accessor.component_type {
+        ComponentType::U8 => Ok(grr::IndexTy::U8),
+        ComponentType::U16 => Ok(grr::IndexTy::U16),
+        ComponentType::U32 => Ok(grr::IndexTy::U32),
+        ty => anyhow::bail!("invalid index component type {:?}", ty),
+    }
+}
+
+/// Read the accessor elements into a tightly packed host array.
+///
+/// Sparse accessors are materialized by substituting the sparse values into
+/// the data of the base buffer view, or into zeros if there is none.
+pub fn read(gltf: &Gltf, accessor: &Accessor) -> anyhow::Result<Vec<u8>> {
+    let element_size = accessor.element_size();
+    let size = accessor
+        .count
+        .checked_mul(element_size)
+        .ok_or_else(|| anyhow::anyhow!("accessor size overflow"))?;
+
+    let mut data = match accessor.buffer_view {
+        Some(view) => {
+            let (src, stride) = view_data(gltf, accessor, view)?;
+            let mut data = Vec::with_capacity(size);
+            for i in 0..accessor.count {
+                let start = accessor.byte_offset + i * stride;
+                data.extend_from_slice(&src[start..start + element_size]);
+            }
+            data
+        }
+        None => vec![0; size],
+    };
+
+    if let Some(ref sparse) = accessor.sparse {
+        let indices = sparse_indices(gltf, sparse)?;
+        let values = sparse
+            .count
+            .checked_mul(element_size)
+            .zip(
+                gltf.buffer_view(sparse.values.buffer_view)?
+                    .get(sparse.values.byte_offset..),
+            )
+            .and_then(|(len, values)| values.get(..len))
+            .ok_or_else(|| anyhow::anyhow!("sparse values out of buffer view bounds"))?;
+
+        for (index, value) in indices.into_iter().zip(values.chunks_exact(element_size)) {
+            anyhow::ensure!(
+                index < accessor.count,
+                "sparse index {} out of accessor bounds ({})",
+                index,
+                accessor.count
+            );
+            data[index * element_size..(index + 1) * element_size].copy_from_slice(value);
+        }
+    }
+
+    Ok(data)
+}
+
+/// Data and stride of the buffer view of a non-sparse accessor.
+///
+/// Checks that all elements of the accessor are within the buffer view, and the
+/// buffer view within its buffer.
+pub fn view_data<'a>(
+    gltf: &'a Gltf,
+    accessor: &Accessor,
+    view: usize,
+) -> anyhow::Result<(&'a [u8], usize)> {
+    let element_size = accessor.element_size();
+    let stride = gltf.document.buffer_views[view]
+        .byte_stride
+        .unwrap_or(element_size);
+    let src = gltf.buffer_view(view)?;
+
+    // Check the last element up front, all other elements are in bounds then.
+    let end = match accessor.count {
+        0 => Some(0),
+        count => (count - 1)
+            .checked_mul(stride)
+            .and_then(|offset| offset.checked_add(accessor.byte_offset))
+            .and_then(|offset| offset.checked_add(element_size)),
+    };
+    anyhow::ensure!(
+        end.map_or(false, |end| end <= src.len()),
+        "accessor out of buffer view bounds"
+    );
+
+    Ok((src, stride))
+}
+
+/// Read the accessor components as floats, normalized integers are mapped to `[0, 1]` or `[-1, 1]`.
+pub fn read_f32(gltf: &Gltf, accessor: &Accessor) -> anyhow::Result<Vec<f32>> {
+    let data = read(gltf, accessor)?;
+    let norm = |x: f32, max: f32| {
+        if accessor.normalized {
+            (x / max).max(-1.0)
+        } else {
+            x
+        }
+    };
+    Ok(match accessor.component_type {
+        ComponentType::F32 => data.chunks_exact(4).map(LittleEndian::read_f32).collect(),
+        ComponentType::I8 => data.iter().map(|&x| norm(x as i8 as f32, 127.0)).collect(),
+        ComponentType::U8 => data.iter().map(|&x| norm(x as f32, 255.0)).collect(),
+        ComponentType::I16 => data
+            .chunks_exact(2)
+            .map(|x| norm(LittleEndian::read_i16(x) as f32, 32767.0))
+            .collect(),
+        ComponentType::U16 => data
+            .chunks_exact(2)
+            .map(|x| norm(LittleEndian::read_u16(x) as f32, 65535.0))
+            .collect(),
+        ComponentType::U32 => data
+            .chunks_exact(4)
+            .map(|x| LittleEndian::read_u32(x) as f32)
+            .collect(),
+    })
+}
+
+/// Read an index accessor as `u32` indices.
+pub fn read_indices(gltf: &Gltf, accessor: usize) -> anyhow::Result<Vec<u32>> {
+    let accessor = &gltf.document.accessors[accessor];
+    let data = read(gltf, accessor)?;
+    match index_ty(accessor)? {
+        grr::IndexTy::U8 => Ok(data.iter().map(|&i| i as u32).collect()),
+        grr::IndexTy::U16 => Ok(data
+            .chunks_exact(2)
+            .map(|i| LittleEndian::read_u16(i) as u32)
+            .collect()),
+        grr::IndexTy::U32 => Ok(data.chunks_exact(4).map(LittleEndian::read_u32).collect()),
+    }
+}
+
+fn sparse_indices(gltf: &Gltf, sparse: &Sparse) -> anyhow::Result<Vec<usize>> {
+    let ty = sparse.indices.component_type;
+    let data = sparse
+        .count
+        .checked_mul(ty.size())
+        .zip(
+            gltf.buffer_view(sparse.indices.buffer_view)?
+                .get(sparse.indices.byte_offset..),
+        )
+        .and_then(|(len, data)| data.get(..len))
+        .ok_or_else(|| anyhow::anyhow!("sparse indices out of buffer view bounds"))?;
+
+    match ty {
+        ComponentType::U8 => Ok(data.iter().map(|&i| i as usize).collect()),
+        ComponentType::U16 => Ok(data
+            .chunks_exact(2)
+            .map(|i| LittleEndian::read_u16(i) as usize)
+            .collect()),
+        ComponentType::U32 => Ok(data
+            .chunks_exact(4)
+            .map(|i| LittleEndian::read_u32(i) as usize)
+            .collect()),
+        ty => anyhow::bail!("invalid sparse index component type {:?}", ty),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gltf::Document;
+    use std::path::PathBuf;
+
+    /// Scalar float accessor of 4 elements `[1, 2, 3, 4]`, with sparse values `[10, 20]`.
+    fn sparse_gltf(component_type: u32, indices: &[u8], base: bool) -> Gltf {
+        let mut buffer = Vec::new();
+        for x in &[1.0f32, 2.0, 3.0, 4.0, 10.0, 20.0] {
+            buffer.extend_from_slice(&x.to_le_bytes());
+        }
+        buffer.extend_from_slice(indices);
+
+        let json = format!(
+            r#"{{
+                "asset": {{"version": "2.0"}},
+                "buffers": [{{"byteLength": {len}}}],
+                "bufferViews": [
+                    {{"buffer": 0, "byteOffset": 0, "byteLength": 16}},
+                    {{"buffer": 0, "byteOffset": 16, "byteLength": 8}},
+                    {{"buffer": 0, "byteOffset": 24, "byteLength": {num_indices}}}
+                ],
+                "accessors": [{{
+                    {view}
+                    "componentType": 5126,
+                    "count": 4,
+                    "type": "SCALAR",
+                    "sparse": {{
+                        "count": 2,
+                        "indices": {{"bufferView": 2, "componentType": {ty}}},
+                        "values": {{"bufferView": 1}}
+                    }}
+                }}]
+            }}"#,
+            len = buffer.len(),
+            num_indices = indices.len(),
+            view = if base { r#""bufferView": 0,"# } else { "" },
+            ty = component_type,
+        );
+        let document: Document = serde_json::from_str(&json).unwrap();
+
+        Gltf {
+            document,
+            buffers: vec![buffer],
+            directory: PathBuf::new(),
+        }
+    }
+
+    /// Test values are small integers, which are exactly representable.
+    fn read_sparse(gltf: &Gltf) -> anyhow::Result<Vec<u32>> {
+        let values = read_f32(gltf, &gltf.document.accessors[0])?;
+        Ok(values.into_iter().map(|value| value as u32).collect())
+    }
+
+    #[test]
+    fn sparse_u8() {
+        let indices = [1, 3];
+        let gltf = sparse_gltf(5121, &indices, true);
+        assert_eq!(read_sparse(&gltf).unwrap(), [1, 10, 3, 20]);
+        let gltf = sparse_gltf(5121, &indices, false);
+        assert_eq!(read_sparse(&gltf).unwrap(), [0, 10, 0, 20]);
+    }
+
+    #[test]
+    fn sparse_u16() {
+        let indices = [0, 0, 2, 0];
+        let gltf = sparse_gltf(5123, &indices, true);
+        assert_eq!(read_sparse(&gltf).unwrap(), [10, 2, 20, 4]);
+        let gltf = sparse_gltf(5123, &indices, false);
+        assert_eq!(read_sparse(&gltf).unwrap(), [10, 0, 20, 0]);
+    }
+
+    #[test]
+    fn sparse_u32() {
+        let indices = [2, 0, 0, 0, 3, 0, 0, 0];
+        let gltf = sparse_gltf(5125, &indices, true);
+        assert_eq!(read_sparse(&gltf).unwrap(), [1, 2, 10, 20]);
+        let gltf = sparse_gltf(5125, &indices, false);
+        assert_eq!(read_sparse(&gltf).unwrap(), [0, 0, 10, 20]);
+    }
+
+    #[test]
+    fn sparse_index_out_of_bounds() {
+        let gltf = sparse_gltf(5121, &[1, 4], true);
+        let err = read_sparse(&gltf).unwrap_err();
+        assert_eq!(err.to_string(), "sparse index 4 out of accessor bounds (4)");
+    }
+
+    #[test]
+    fn sparse_invalid_component_type() {
+        let gltf = sparse_gltf(5122, &[1, 0, 3, 0], true);
+        let err = read_sparse(&gltf).unwrap_err();
+        assert_eq!(err.to_string(), "invalid sparse index component type I16");
+    }
+
+    /// Vec2 u8 accessor of `count` elements in a buffer view with stride 4.
+    fn strided_gltf(byte_offset: usize, count: usize) -> Gltf {
+        let json = format!(
+            r#"{{
+                "asset": {{"version": "2.0"}},
+                "buffers": [{{"byteLength": 12}}],
+                "bufferViews": [{{"buffer": 0, "byteOffset": 2, "byteLength": 10, "byteStride": 4}}],
+                "accessors": [{{
+                    "bufferView": 0,
+                    "byteOffset": {},
+                    "componentType": 5121,
+                    "count": {},
+                    "type": "VEC2"
+                }}]
+            }}"#,
+            byte_offset, count,
+        );
+        Gltf {
+            document: serde_json::from_str(&json).unwrap(),
+            buffers: vec![(0..12).collect()],
+            directory: PathBuf::new(),
+        }
+    }
+
+    #[test]
+    fn strided() {
+        let gltf = strided_gltf(0, 3);
+        let data = read(&gltf, &gltf.document.accessors[0]).unwrap();
+        assert_eq!(data, [2, 3, 6, 7, 10, 11]);
+    }
+
+    #[test]
+    fn strided_out_of_bounds() {
+        // The last element starts within the buffer view, but ends past it.
+        let gltf = strided_gltf(1, 3);
+        let err = view_data(&gltf, &gltf.document.accessors[0], 0).unwrap_err();
+        assert_eq!(err.to_string(), "accessor out of buffer view bounds");
+        let err = read(&gltf, &gltf.document.accessors[0]).unwrap_err();
+        assert_eq!(err.to_string(), "accessor out of buffer view bounds");
+    }
+}
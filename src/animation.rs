@@ -0,0 +1,482 @@
+use crate::accessor;
+use crate::gltf::{self, AccessorType, Gltf, Interpolation, TargetPath};
+use crate::scene::{Scene, Transform};
+use std::time::Instant;
+
+/// Keyframes of a single animated property.
+struct Sampler {
+    interpolation: Interpolation,
+    times: Vec<f32>,
+    /// Keyframe values, cubic splines store `(in_tangent, value, out_tangent)` for each key.
+    values: Vec<f32>,
+    components: usize,
+}
+
+impl Sampler {
+    fn new(
+        model: &Gltf,
+        sampler: &gltf::AnimationSampler,
+        path: TargetPath,
+    ) -> anyhow::Result<Self> {
+        let input = &model.document.accessors[sampler.input];
+        let output = &model.document.accessors[sampler.output];
+        anyhow::ensure!(
+            input.ty == AccessorType::Scalar,
+            "animation input must be scalar"
+        );
+        anyhow::ensure!(input.count > 0, "animation sampler without keyframes");
+
+        let times = accessor::read_f32(model, input)?;
+        let values = accessor::read_f32(model, output)?;
+
+        let elements = match sampler.interpolation {
+            Interpolation::CubicSpline => 3 * times.len(),
+            Interpolation::Linear | Interpolation::Step => times.len(),
+        };
+        let components = match path {
+            TargetPath::Translation | TargetPath::Scale => 3,
+            TargetPath::Rotation => 4,
+            TargetPath::Weights => values.len() / elements,
+        };
+        anyhow::ensure!(
+            values.len() == elements * components,
+            "animation output doesn't match the number of keyframes"
+        );
+
+        Ok(Sampler {
+            interpolation: sampler.interpolation,
+            times,
+            values,
+            components,
+        })
+    }
+
+    fn duration(&self) -> f32 {
+        self.times[self.times.len() - 1]
+    }
+
+    fn element(&self, i: usize) -> &[f32] {
+        &self.values[i * self.components..(i + 1) * self.components]
+    }
+
+    fn value(&self, key: usize) -> &[f32] {
+        match self.interpolation {
+            Interpolation::CubicSpline => self.element(3 * key + 1),
+            Interpolation::Linear | Interpolation::Step => self.element(key),
+        }
+    }
+
+    /// Evaluate the sampler at time `t`, rotations are returned normalized.
+    fn sample(&self, t: f32, rotation: bool) -> Vec<f32> {
+        let last = self.times.len() - 1;
+        if t <= self.times[0] {
+            return self.value(0).to_vec();
+        }
+        if t >= self.times[last] {
+            return self.value(last).to_vec();
+        }
+
+        // Keyframe interval `[times[key], times[key + 1])` containing `t`.
+        let key = self.times.partition_point(|&time| time <= t) - 1;
+        let dt = self.times[key + 1] - self.times[key];
+        let s = (t - self.times[key]) / dt;
+
+        match self.interpolation {
+            Interpolation::Step => self.value(key).to_vec(),
+            Interpolation::Linear if rotation => slerp(self.value(key), self.value(key + 1), s),
+            Interpolation::Linear => self
+                .value(key)
+                .iter()
+                .zip(self.value(key + 1))
+                .map(|(a, b)| a + (b - a) * s)
+                .collect(),
+            Interpolation::CubicSpline => {
+                let (s2, s3) = (s * s, s * s * s);
+                let v0 = self.value(key);
+                let b0 = self.element(3 * key + 2);
+                let v1 = self.value(key + 1);
+                let a1 = self.element(3 * (key + 1));
+
+                let value = (0..self.components)
+                    .map(|i| {
+                        (2.0 * s3 - 3.0 * s2 + 1.0) * v0[i]
+                            + (s3 - 2.0 * s2 + s) * dt * b0[i]
+                            + (-2.0 * s3 + 3.0 * s2) * v1[i]
+                            + (s3 - s2) * dt * a1[i]
+                    })
+                    .collect::<Vec<_>>();
+                if rotation {
+                    normalize(&value)
+                } else {
+                    value
+                }
+            }
+        }
+    }
+}
+
+fn normalize(q: &[f32]) -> Vec<f32> {
+    let len = q.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if len > 0.0 {
+        q.iter().map(|x| x / len).collect()
+    } else {
+        q.to_vec()
+    }
+}
+
+/// Spherical linear interpolation of unit quaternions along the shortest path.
+fn slerp(a: &[f32], b: &[f32], t: f32) -> Vec<f32> {
+    let mut cos = a.iter().zip(b).map(|(a, b)| a * b).sum::<f32>();
+    let sign = if cos < 0.0 { -1.0 } else { 1.0 };
+    cos *= sign;
+
+    let (wa, wb) = if cos > 0.9995 {
+        // Nearly parallel, fall back to normalized linear interpolation.
+        (1.0 - t, t)
+    } else {
+        let theta = cos.acos();
+        let sin = theta.sin();
+        (((1.0 - t) * theta).sin() / sin, (t * theta).sin() / sin)
+    };
+
+    let q = a
+        .iter()
+        .zip(b)
+        .map(|(a, b)| wa * a + wb * sign * b)
+        .collect::<Vec<_>>();
+    normalize(&q)
+}
+
+struct Channel {
+    node: usize,
+    path: TargetPath,
+    sampler: Sampler,
+}
+
+pub struct Animation {
+    channels: Vec<Channel>,
+    duration: f32,
+}
+
+impl Animation {
+    pub fn new(model: &Gltf, animation: &gltf::Animation) -> anyhow::Result<Self> {
+        let mut channels = Vec::new();
+        for channel in &animation.channels {
+            // Channels without node are targeting extensions.
+            let node = match channel.target.node {
+                Some(node) => node,
+                None => continue,
+            };
+            anyhow::ensure!(
+                node < model.document.nodes.len(),
+                "animation target out of node bounds"
+            );
+            let sampler = animation
+                .samplers
+                .get(channel.sampler)
+                .ok_or_else(|| anyhow::anyhow!("animation sampler out of bounds"))?;
+            channels.push(Channel {
+                node,
+                path: channel.target.path,
+                sampler: Sampler::new(model, sampler, channel.target.path)?,
+            });
+        }
+
+        let duration = channels
+            .iter()
+            .map(|channel| channel.sampler.duration())
+            .fold(0.0, f32::max);
+
+        Ok(Animation { channels, duration })
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.duration
+    }
+
+    /// Update the animated node properties to the state at time `t`.
+    pub fn apply(&self, t: f32, scene: &mut Scene) {
+        for channel in &self.channels {
+            let value = channel
+                .sampler
+                .sample(t, channel.path == TargetPath::Rotation);
+            let node = &mut scene.nodes[channel.node];
+            if channel.path == TargetPath::Weights {
+                node.weights = value;
+                continue;
+            }
+
+            // Animated nodes are required to be defined by TRS properties.
+            if let Transform::Trs {
+                ref mut translation,
+                ref mut rotation,
+                ref mut scale,
+            } = node.transform
+            {
+                match channel.path {
+                    TargetPath::Translation => translation.copy_from_slice(&value),
+                    TargetPath::Rotation => rotation.copy_from_slice(&value),
+                    TargetPath::Scale => scale.copy_from_slice(&value),
+                    TargetPath::Weights => unreachable!(),
+                }
+            }
+        }
+    }
+}
+
+pub fn load_animations(model: &Gltf) -> anyhow::Result<Vec<Animation>> {
+    model
+        .document
+        .animations
+        .iter()
+        .map(|animation| Animation::new(model, animation))
+        .collect()
+}
+
+/// Playback clock advancing with the real elapsed time.
+pub struct Clock {
+    last: Instant,
+    time: f32,
+    duration: f32,
+    pub speed: f32,
+    pub playing: bool,
+    pub looping: bool,
+}
+
+impl Clock {
+    pub fn new(duration: f32) -> Self {
+        Clock {
+            last: Instant::now(),
+            time: 0.0,
+            duration,
+            speed: 1.0,
+            playing: true,
+            looping: true,
+        }
+    }
+
+    pub fn play(&mut self) {
+        // Restart finished playback.
+        if !self.looping && self.time >= self.duration {
+            self.time = 0.0;
+        }
+        // Ticks stop while paused, don't count the paused time.
+        self.last = Instant::now();
+        self.playing = true;
+    }
+
+    pub fn pause(&mut self) {
+        self.playing = false;
+    }
+
+    /// Advance the clock by the real elapsed time, either wrapping around or stopping at the end.
+    pub fn tick(&mut self) -> f32 {
+        let now = Instant::now();
+        let elapsed = (now - self.last).as_secs_f32();
+        self.last = now;
+        self.advance(elapsed)
+    }
+
+    fn advance(&mut self, elapsed: f32) -> f32 {
+        if self.playing {
+            self.time += elapsed * self.speed;
+            if self.duration <= 0.0 {
+                self.time = 0.0;
+            } else if self.looping {
+                self.time = self.time.rem_euclid(self.duration);
+            } else if self.time >= self.duration {
+                self.time = self.duration;
+                self.playing = false;
+            }
+        }
+        self.time
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::FRAC_1_SQRT_2;
+    use std::time::Duration;
+
+    fn keyframes(interpolation: Interpolation, times: &[f32], values: &[f32]) -> Sampler {
+        let elements = match interpolation {
+            Interpolation::CubicSpline => 3 * times.len(),
+            Interpolation::Linear | Interpolation::Step => times.len(),
+        };
+        Sampler {
+            interpolation,
+            times: times.to_vec(),
+            values: values.to_vec(),
+            components: values.len() / elements,
+        }
+    }
+
+    fn assert_close(actual: &[f32], expected: &[f32]) {
+        assert_eq!(actual.len(), expected.len());
+        assert!(
+            actual
+                .iter()
+                .zip(expected)
+                .all(|(a, b)| (a - b).abs() < 1e-5),
+            "{:?} != {:?}",
+            actual,
+            expected
+        );
+    }
+
+    /// Check samples at `(time, expected value)` pairs.
+    fn check(sampler: &Sampler, rotation: bool, samples: &[(f32, &[f32])]) {
+        for &(t, expected) in samples {
+            assert_close(&sampler.sample(t, rotation), expected);
+        }
+    }
+
+    #[test]
+    fn step() {
+        let sampler = keyframes(Interpolation::Step, &[0.0, 1.0, 2.0], &[0.0, 10.0, 20.0]);
+        check(
+            &sampler,
+            false,
+            &[
+                (-1.0, &[0.0]),
+                (0.5, &[0.0]),
+                (1.0, &[10.0]),
+                (1.9, &[10.0]),
+                (3.0, &[20.0]),
+            ],
+        );
+    }
+
+    #[test]
+    fn linear() {
+        let sampler = keyframes(
+            Interpolation::Linear,
+            &[1.0, 2.0, 4.0],
+            &[0.0, 0.0, 0.0, 10.0, 20.0, 30.0, 20.0, 0.0, 30.0],
+        );
+        check(
+            &sampler,
+            false,
+            &[
+                (0.0, &[0.0, 0.0, 0.0]),
+                (1.5, &[5.0, 10.0, 15.0]),
+                (3.0, &[15.0, 10.0, 30.0]),
+                (5.0, &[20.0, 0.0, 30.0]),
+            ],
+        );
+    }
+
+    #[test]
+    fn cubic_spline() {
+        // `(in_tangent, value, out_tangent)` per key, tangents are scaled by the interval of 2s.
+        let sampler = keyframes(
+            Interpolation::CubicSpline,
+            &[1.0, 3.0],
+            &[-5.0, 0.0, 1.0, 0.0, 1.0, 5.0],
+        );
+        check(
+            &sampler,
+            false,
+            &[
+                (0.0, &[0.0]),
+                (1.0, &[0.0]),
+                (2.0, &[0.75]),
+                (3.0, &[1.0]),
+                (4.0, &[1.0]),
+            ],
+        );
+    }
+
+    #[test]
+    fn slerp_rotation() {
+        // Identity to 90 degrees around Z.
+        let (sin, cos) = (FRAC_1_SQRT_2, FRAC_1_SQRT_2);
+        // 22.5 and 45 degrees, linear interpolation would deviate from these at a quarter.
+        let quarter = [0.0, 0.0, 0.19509032, 0.98078525];
+        let half = [0.0, 0.0, 0.38268343, 0.9238795];
+        let sampler = keyframes(
+            Interpolation::Linear,
+            &[0.0, 1.0],
+            &[0.0, 0.0, 0.0, 1.0, 0.0, 0.0, sin, cos],
+        );
+        check(
+            &sampler,
+            true,
+            &[
+                (-1.0, &[0.0, 0.0, 0.0, 1.0]),
+                (0.25, &quarter),
+                (0.5, &half),
+                (2.0, &[0.0, 0.0, sin, cos]),
+            ],
+        );
+
+        // The negated quaternion is the same rotation, interpolated along the shortest path.
+        let sampler = keyframes(
+            Interpolation::Linear,
+            &[0.0, 1.0],
+            &[0.0, 0.0, 0.0, 1.0, 0.0, 0.0, -sin, -cos],
+        );
+        check(&sampler, true, &[(0.25, &quarter), (0.5, &half)]);
+    }
+
+    #[test]
+    fn cubic_spline_rotation_normalized() {
+        // Zero tangents, identity to 90 degrees around Z.
+        let values = [
+            [0.0; 4],
+            [0.0, 0.0, 0.0, 1.0],
+            [0.0; 4],
+            [0.0; 4],
+            [0.0, 0.0, FRAC_1_SQRT_2, FRAC_1_SQRT_2],
+            [0.0; 4],
+        ]
+        .concat();
+        let sampler = keyframes(Interpolation::CubicSpline, &[0.0, 1.0], &values);
+        let q = sampler.sample(0.5, true);
+        assert!((q.iter().map(|x| x * x).sum::<f32>() - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn clock_looping() {
+        let mut clock = Clock::new(2.0);
+        assert_close(&[clock.advance(1.5)], &[1.5]);
+        assert_close(&[clock.advance(1.0)], &[0.5]);
+        assert!(clock.playing);
+    }
+
+    #[test]
+    fn clock_speed() {
+        let mut clock = Clock::new(10.0);
+        clock.speed = 2.0;
+        assert_close(&[clock.advance(1.5)], &[3.0]);
+        clock.speed = 0.5;
+        assert_close(&[clock.advance(1.0)], &[3.5]);
+    }
+
+    #[test]
+    fn clock_stop_and_restart() {
+        let mut clock = Clock::new(2.0);
+        clock.looping = false;
+        assert_close(&[clock.advance(3.0)], &[2.0]);
+        assert!(!clock.playing);
+        assert_close(&[clock.advance(1.0)], &[2.0]);
+
+        clock.play();
+        assert!(clock.playing);
+        assert_close(&[clock.advance(0.5)], &[0.5]);
+    }
+
+    #[test]
+    fn clock_pause_resume() {
+        let mut clock = Clock::new(100.0);
+        clock.advance(1.0);
+        clock.pause();
+        assert_close(&[clock.advance(5.0)], &[1.0]);
+
+        // Without ticks while paused, resuming must not catch up on the paused time.
+        clock.last -= Duration::from_secs(10);
+        clock.play();
+        assert!(clock.tick() < 2.0);
+    }
+}
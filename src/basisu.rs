@@ -0,0 +1,777 @@
+//! Basis Universal transcoding of KTX2 images.
+//!
+//! BasisLZ/ETC1S images are decoded natively using the codebooks stored in the supercompression
+//! global data. UASTC blocks are passed to the reference transcoder.
+//!
+//! Images are always transcoded to uncompressed RGBA8, even if the context supports BC7 or
+//! ETC2. `grr::Format` has no compressed variants and `copy_host_to_image` only issues
+//! `glTextureSubImage*`, never `glCompressedTextureSubImage*`. Raw GL can't fill the gap, as
+//! `grr::Image` doesn't expose its texture name. Block format targets need these in `grr` first.
+
+use crate::ktx;
+use basis_universal::{
+    DecodeFlags, LowLevelUastcTranscoder, SliceParametersUastc, TranscoderBlockFormat,
+};
+use byteorder::{LittleEndian, ReadBytesExt};
+
+/// Largest texture size OpenGL 4.5 implementations have to support.
+const MAX_SIZE: u32 = 16384;
+
+/// Decode a level of a Basis Universal image (first layer and face) into RGBA8 pixels.
+pub fn decode_rgba8(image: &ktx::Image, level: usize) -> anyhow::Result<Vec<u8>> {
+    let header = &image.header;
+    let data = image
+        .levels
+        .get(level)
+        .ok_or_else(|| anyhow::anyhow!("level {} out of bounds", level))?;
+    // `level` is bounded by the level count read from a `u32`.
+    let width = mip_size(header.pixel_width, level as u32);
+    let height = mip_size(header.pixel_height, level as u32);
+    anyhow::ensure!(
+        width <= MAX_SIZE && height <= MAX_SIZE,
+        "image size {}x{} exceeds {}",
+        width,
+        height,
+        MAX_SIZE
+    );
+
+    match image.dfd.color_model {
+        ktx::COLOR_MODEL_ETC1S => {
+            anyhow::ensure!(
+                header.supercompression_scheme == ktx::SUPERCOMPRESSION_BASIS_LZ,
+                "ETC1S requires BasisLZ supercompression"
+            );
+            let global_data = GlobalData::new(image)?;
+            global_data.decode_rgba8(level, data, width, height)
+        }
+        ktx::COLOR_MODEL_UASTC => {
+            let has_alpha = image.dfd.samples.iter().any(|sample| {
+                sample.channel_type == ktx::CHANNEL_UASTC_RGBA
+                    || sample.channel_type == ktx::CHANNEL_UASTC_RRRG
+            });
+            decode_uastc_rgba8(data, width, height, has_alpha)
+        }
+        model => anyhow::bail!(
+            "image isn't Basis Universal compressed (color model {})",
+            model
+        ),
+    }
+}
+
+/// Size of a mip level in one dimension, levels past the smallest one are clamped to 1.
+fn mip_size(size: u32, level: u32) -> u32 {
+    size.checked_shr(level).unwrap_or(0).max(1)
+}
+
+/// Number of 4x4 blocks covering the size in one dimension.
+fn num_blocks(size: u32) -> u32 {
+    size / 4 + (size % 4 != 0) as u32
+}
+
+fn decode_uastc_rgba8(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    has_alpha: bool,
+) -> anyhow::Result<Vec<u8>> {
+    let num_blocks_x = num_blocks(width);
+    let num_blocks_y = num_blocks(height);
+    let len = (num_blocks_x as usize)
+        .checked_mul(num_blocks_y as usize)
+        .and_then(|num_blocks| num_blocks.checked_mul(16))
+        .filter(|&len| len <= data.len())
+        .ok_or_else(|| anyhow::anyhow!("UASTC level too small"))?;
+
+    // `transcode_slice` derives the output row pitch in blocks even for uncompressed formats,
+    // pretending a 4x wider image makes it match the padded width in pixels.
+    let original_width = num_blocks_x
+        .checked_mul(16)
+        .ok_or_else(|| anyhow::anyhow!("UASTC level too wide"))?;
+    let padded_width = original_width / 4;
+    let transcoder = LowLevelUastcTranscoder::new();
+    let padded = transcoder
+        .transcode_slice(
+            &data[..len],
+            SliceParametersUastc {
+                num_blocks_x,
+                num_blocks_y,
+                has_alpha,
+                original_width,
+                original_height: 4 * num_blocks_y,
+            },
+            DecodeFlags::HIGH_QUALITY,
+            TranscoderBlockFormat::RGBA32,
+        )
+        .map_err(|err| anyhow::anyhow!("failed to transcode UASTC level: {:?}", err))?;
+
+    let row_len = 4 * width as usize;
+    Ok(padded
+        .chunks_exact(4 * padded_width as usize)
+        .take(height as usize)
+        .flat_map(|row| &row[..row_len])
+        .copied()
+        .collect())
+}
+
+/// ETC1 intensity modifiers, indexed by the ETC1S selector value.
+const INTENSITY_MODIFIERS: [[i32; 4]; 8] = [
+    [-8, -2, 2, 8],
+    [-17, -5, 5, 17],
+    [-29, -9, 9, 29],
+    [-42, -13, 13, 42],
+    [-60, -18, 18, 60],
+    [-80, -24, 24, 80],
+    [-106, -33, 33, 106],
+    [-183, -47, 47, 183],
+];
+
+// Huffman table encoding
+const MAX_CODE_SIZE: usize = 16;
+const CODE_LENGTH_CODES: usize = 21;
+const SMALL_ZERO_RUN_CODE: u32 = 17;
+const BIG_ZERO_RUN_CODE: u32 = 18;
+const SMALL_REPEAT_CODE: u32 = 19;
+const CODE_LENGTH_CODE_ORDER: [usize; CODE_LENGTH_CODES] = [
+    17, 18, 19, 20, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15, 16,
+];
+
+// Endpoint palette
+const COLOR5_PAL0_PREV_HI: u8 = 9;
+const COLOR5_PAL1_PREV_HI: u8 = 21;
+
+// Slice decoding
+const ENDPOINT_PRED_REPEAT_LAST_SYMBOL: u32 = 4 * 4 * 4 * 4;
+const ENDPOINT_PRED_MIN_REPEAT_COUNT: u32 = 3;
+const ENDPOINT_PRED_COUNT_VLC_BITS: u32 = 4;
+const SELECTOR_HISTORY_BUF_RLE_COUNT_THRESH: u32 = 3;
+const SELECTOR_HISTORY_BUF_RLE_COUNT_TOTAL: u32 = 1 << 6;
+
+/// LSB-first bit reader, reading zeros past the end of the data.
+struct BitReader<'a> {
+    data: &'a [u8],
+    buf: u32,
+    len: u32,
+    /// Whether bits past the end of the data have been requested.
+    overrun: bool,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        BitReader {
+            data,
+            buf: 0,
+            len: 0,
+            overrun: false,
+        }
+    }
+
+    fn read(&mut self, bits: u32) -> u32 {
+        while self.len < bits {
+            let byte = match self.data.split_first() {
+                Some((&byte, rest)) => {
+                    self.data = rest;
+                    byte
+                }
+                None => {
+                    self.overrun = true;
+                    0
+                }
+            };
+            self.buf |= (byte as u32) << self.len;
+            self.len += 8;
+        }
+        let value = self.buf & ((1 << bits) - 1);
+        self.buf >>= bits;
+        self.len -= bits;
+        value
+    }
+
+    /// Variable length integer split into chunks, each followed by a continuation bit.
+    fn read_vlc(&mut self, chunk_bits: u32) -> anyhow::Result<u32> {
+        let mut value = 0;
+        let mut shift = 0;
+        loop {
+            let chunk = self.read(chunk_bits + 1);
+            value |= (chunk & ((1 << chunk_bits) - 1)) << shift;
+            shift += chunk_bits;
+            if chunk & (1 << chunk_bits) == 0 {
+                return Ok(value);
+            }
+            anyhow::ensure!(shift < 32, "invalid variable length integer");
+        }
+    }
+
+    fn read_huffman(&mut self, table: &Huffman) -> anyhow::Result<u32> {
+        table.decode(self)
+    }
+}
+
+/// Canonical Huffman decoding table.
+struct Huffman {
+    /// Number of codes for each code length.
+    counts: [u32; MAX_CODE_SIZE + 1],
+    /// Symbols ordered by code length.
+    symbols: Vec<u32>,
+}
+
+impl Huffman {
+    fn new(code_sizes: &[u8]) -> anyhow::Result<Self> {
+        let mut counts = [0; MAX_CODE_SIZE + 1];
+        for &size in code_sizes {
+            counts[size as usize] += 1;
+        }
+        counts[0] = 0;
+
+        let mut left = 1i64;
+        for &count in &counts[1..] {
+            left = (left << 1) - count as i64;
+            anyhow::ensure!(left >= 0, "oversubscribed huffman table");
+        }
+
+        let mut symbols = (0..code_sizes.len() as u32)
+            .filter(|&symbol| code_sizes[symbol as usize] > 0)
+            .collect::<Vec<_>>();
+        symbols.sort_by_key(|&symbol| code_sizes[symbol as usize]);
+
+        Ok(Huffman { counts, symbols })
+    }
+
+    /// Read a table stored as code lengths, which are Huffman coded themselves.
+    fn read(reader: &mut BitReader) -> anyhow::Result<Self> {
+        let num_symbols = reader.read(14) as usize;
+        if num_symbols == 0 {
+            return Huffman::new(&[]);
+        }
+
+        let num_code_length_codes = reader.read(5) as usize;
+        anyhow::ensure!(
+            (1..=CODE_LENGTH_CODES).contains(&num_code_length_codes),
+            "invalid number of code length codes"
+        );
+        let mut code_length_sizes = [0; CODE_LENGTH_CODES];
+        for &code in &CODE_LENGTH_CODE_ORDER[..num_code_length_codes] {
+            code_length_sizes[code] = reader.read(3) as u8;
+        }
+        let code_lengths = Huffman::new(&code_length_sizes)?;
+
+        let mut code_sizes = Vec::with_capacity(num_symbols);
+        while code_sizes.len() < num_symbols {
+            let code = reader.read_huffman(&code_lengths)?;
+            let (size, len) = match code {
+                0..=16 => (code as u8, 1),
+                SMALL_ZERO_RUN_CODE => (0, reader.read(3) + 3),
+                BIG_ZERO_RUN_CODE => (0, reader.read(7) + 11),
+                _ => {
+                    let prev = code_sizes.last().copied().unwrap_or(0);
+                    anyhow::ensure!(prev != 0, "repeat of a zero code length");
+                    let len = if code == SMALL_REPEAT_CODE {
+                        reader.read(2) + 3
+                    } else {
+                        reader.read(7) + 7
+                    };
+                    (prev, len)
+                }
+            };
+            anyhow::ensure!(
+                code_sizes.len() + len as usize <= num_symbols,
+                "code lengths exceed the number of symbols"
+            );
+            code_sizes.extend(std::iter::repeat(size).take(len as usize));
+        }
+
+        Huffman::new(&code_sizes)
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> anyhow::Result<u32> {
+        // Codes are stored bit reversed, the first bit read is the most significant one.
+        let mut code = 0;
+        let mut first = 0;
+        let mut index = 0;
+        for &count in &self.counts[1..] {
+            code |= reader.read(1);
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        anyhow::bail!("invalid huffman code")
+    }
+}
+
+#[derive(Debug, Copy, Clone, Default)]
+struct Endpoint {
+    color5: [u8; 3],
+    intensity: u8,
+}
+
+impl Endpoint {
+    /// The four colors selectable in a block.
+    fn colors(&self) -> [[u8; 3]; 4] {
+        let mut colors = [[0; 3]; 4];
+        let modifiers = INTENSITY_MODIFIERS[self.intensity as usize];
+        for (color, modifier) in colors.iter_mut().zip(&modifiers) {
+            for (c, &color5) in color.iter_mut().zip(&self.color5) {
+                let base = ((color5 << 3) | (color5 >> 2)) as i32;
+                *c = (base + modifier).clamp(0, 255) as u8;
+            }
+        }
+        colors
+    }
+}
+
+/// 2-bit selectors of a 4x4 block, one byte per row.
+type Selector = [u8; 4];
+
+/// Move-to-front like cache of recently used selectors.
+struct SelectorHistory {
+    values: Vec<u32>,
+    rover: usize,
+}
+
+impl SelectorHistory {
+    fn new(len: usize) -> Self {
+        SelectorHistory {
+            values: vec![0; len],
+            rover: len / 2,
+        }
+    }
+
+    fn add(&mut self, value: u32) {
+        self.values[self.rover] = value;
+        self.rover += 1;
+        if self.rover == self.values.len() {
+            self.rover = self.values.len() / 2;
+        }
+    }
+
+    fn use_index(&mut self, index: usize) {
+        self.values.swap(index / 2, index);
+    }
+}
+
+/// Tables shared by all slices.
+struct Tables {
+    endpoint_pred: Huffman,
+    delta_endpoint: Huffman,
+    selector: Huffman,
+    selector_history_rle: Huffman,
+    selector_history_len: usize,
+}
+
+/// Slice location inside the level data.
+#[derive(Debug, Copy, Clone)]
+struct ImageDesc {
+    rgb: (usize, usize),
+    alpha: (usize, usize),
+}
+
+/// BasisLZ supercompression global data.
+struct GlobalData {
+    images: Vec<ImageDesc>,
+    /// Number of images in each level.
+    images_per_level: usize,
+    endpoints: Vec<Endpoint>,
+    selectors: Vec<Selector>,
+    tables: Tables,
+}
+
+impl GlobalData {
+    fn new(image: &ktx::Image) -> anyhow::Result<Self> {
+        let header = &image.header;
+        let mut rdr = std::io::Cursor::new(&image.global_data[..]);
+        let endpoint_count = rdr.read_u16::<LittleEndian>()? as usize;
+        let selector_count = rdr.read_u16::<LittleEndian>()? as usize;
+        let endpoints_len = rdr.read_u32::<LittleEndian>()? as usize;
+        let selectors_len = rdr.read_u32::<LittleEndian>()? as usize;
+        let tables_len = rdr.read_u32::<LittleEndian>()? as usize;
+        let _extended_len = rdr.read_u32::<LittleEndian>()?;
+
+        let images_per_level = (header.layer_count.max(1) as usize)
+            .checked_mul(header.face_count as usize)
+            .filter(|&count| count > 0)
+            .ok_or_else(|| anyhow::anyhow!("invalid layer or face count"))?;
+        let image_count = (0..header.level_count.max(1))
+            .try_fold(0usize, |count, level| {
+                images_per_level
+                    .checked_mul(mip_size(header.pixel_depth, level) as usize)
+                    .and_then(|images| count.checked_add(images))
+            })
+            .ok_or_else(|| anyhow::anyhow!("too many images"))?;
+        // Each image descriptor takes 20 bytes.
+        anyhow::ensure!(
+            image_count <= image.global_data.len() / 20,
+            "truncated supercompression global data"
+        );
+        let images = (0..image_count)
+            .map(|_| {
+                let _flags = rdr.read_u32::<LittleEndian>()?;
+                let rgb_offset = rdr.read_u32::<LittleEndian>()? as usize;
+                let rgb_len = rdr.read_u32::<LittleEndian>()? as usize;
+                let alpha_offset = rdr.read_u32::<LittleEndian>()? as usize;
+                let alpha_len = rdr.read_u32::<LittleEndian>()? as usize;
+                Ok(ImageDesc {
+                    rgb: (rgb_offset, rgb_len),
+                    alpha: (alpha_offset, alpha_len),
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let data = &image.global_data[rdr.position() as usize..];
+        anyhow::ensure!(
+            data.len() >= endpoints_len + selectors_len + tables_len,
+            "truncated supercompression global data"
+        );
+        let (endpoints_data, data) = data.split_at(endpoints_len);
+        let (selectors_data, data) = data.split_at(selectors_len);
+        let tables_data = &data[..tables_len];
+
+        Ok(GlobalData {
+            images,
+            images_per_level,
+            endpoints: decode_endpoints(endpoints_data, endpoint_count)?,
+            selectors: decode_selectors(selectors_data, selector_count)?,
+            tables: decode_tables(tables_data)?,
+        })
+    }
+
+    fn decode_rgba8(
+        &self,
+        level: usize,
+        data: &[u8],
+        width: u32,
+        height: u32,
+    ) -> anyhow::Result<Vec<u8>> {
+        let desc = level
+            .checked_mul(self.images_per_level)
+            .and_then(|index| self.images.get(index))
+            .ok_or_else(|| anyhow::anyhow!("missing image descriptor for level {}", level))?;
+        let num_blocks_x = num_blocks(width) as usize;
+        let num_blocks_y = num_blocks(height) as usize;
+        let slice = |(offset, len): (usize, usize)| {
+            offset
+                .checked_add(len)
+                .and_then(|end| data.get(offset..end))
+                .ok_or_else(|| anyhow::anyhow!("slice out of level bounds"))
+        };
+
+        let rgb = self.decode_slice(slice(desc.rgb)?, num_blocks_x, num_blocks_y)?;
+        let alpha = match desc.alpha {
+            (_, 0) => None,
+            range => Some(self.decode_slice(slice(range)?, num_blocks_x, num_blocks_y)?),
+        };
+
+        let (width, height) = (width as usize, height as usize);
+        // All blocks were decoded from the level data before allocating the pixels.
+        let len = width
+            .checked_mul(height)
+            .and_then(|len| len.checked_mul(4))
+            .ok_or_else(|| anyhow::anyhow!("image too large"))?;
+        let mut pixels = vec![255; len];
+        for (i, &(endpoint, selector)) in rgb.iter().enumerate() {
+            let (block_x, block_y) = (i % num_blocks_x, i / num_blocks_x);
+            let colors = self.endpoints[endpoint].colors();
+            let selector = &self.selectors[selector];
+            let alpha = alpha.as_ref().map(|alpha| {
+                let (endpoint, selector) = alpha[i];
+                (self.endpoints[endpoint].colors(), &self.selectors[selector])
+            });
+
+            for y in 0..4 {
+                for x in 0..4 {
+                    let (px, py) = (4 * block_x + x, 4 * block_y + y);
+                    if px >= width || py >= height {
+                        continue;
+                    }
+                    let texel = &mut pixels[4 * (py * width + px)..][..4];
+                    let [r, g, b] = colors[(selector[y] >> (2 * x) & 3) as usize];
+                    texel[..3].copy_from_slice(&[r, g, b]);
+                    // Alpha slices are stored as grayscale.
+                    if let Some((colors, selector)) = alpha {
+                        texel[3] = colors[(selector[y] >> (2 * x) & 3) as usize][1];
+                    }
+                }
+            }
+        }
+
+        Ok(pixels)
+    }
+
+    /// Decode the endpoint and selector indices of all blocks in the slice.
+    fn decode_slice(
+        &self,
+        data: &[u8],
+        num_blocks_x: usize,
+        num_blocks_y: usize,
+    ) -> anyhow::Result<Vec<(usize, usize)>> {
+        let tables = &self.tables;
+        let num_endpoints = self.endpoints.len();
+        let num_selectors = self.selectors.len();
+        let total_blocks = num_blocks_x * num_blocks_y;
+
+        let history_rle_symbol = num_selectors + tables.selector_history_len;
+        let mut history = SelectorHistory::new(tables.selector_history_len);
+        let mut selector_rle_count = 0;
+
+        let mut reader = BitReader::new(data);
+        // Blocks take at least one bit unless run length coded, don't trust the size up front.
+        let mut blocks: Vec<(usize, usize)> = Vec::with_capacity(total_blocks.min(8 * data.len()));
+
+        // Endpoint predictors are coded for 2x2 blocks, the lower row is stored for the next row.
+        let mut pred_bits_next_row = vec![0; num_blocks_x];
+        let mut pred_bits = 0;
+        let mut prev_pred_symbol = 0;
+        let mut pred_repeat_count = 0;
+        let mut prev_endpoint = 0;
+
+        for block_y in 0..num_blocks_y {
+            for (block_x, next_row_pred_bits) in pred_bits_next_row.iter_mut().enumerate() {
+                if block_x % 2 == 0 {
+                    if block_y % 2 == 0 {
+                        if pred_repeat_count > 0 {
+                            pred_repeat_count -= 1;
+                            pred_bits = prev_pred_symbol;
+                        } else {
+                            pred_bits = reader.read_huffman(&tables.endpoint_pred)?;
+                            if pred_bits == ENDPOINT_PRED_REPEAT_LAST_SYMBOL {
+                                pred_repeat_count = reader
+                                    .read_vlc(ENDPOINT_PRED_COUNT_VLC_BITS)?
+                                    .saturating_add(ENDPOINT_PRED_MIN_REPEAT_COUNT - 1);
+                                anyhow::ensure!(
+                                    pred_repeat_count as usize <= total_blocks,
+                                    "endpoint prediction run exceeds the slice"
+                                );
+                                pred_bits = prev_pred_symbol;
+                            } else {
+                                prev_pred_symbol = pred_bits;
+                            }
+                        }
+                        *next_row_pred_bits = pred_bits >> 4;
+                    } else {
+                        pred_bits = *next_row_pred_bits;
+                    }
+                }
+
+                let pred = pred_bits & 3;
+                pred_bits >>= 2;
+                let endpoint = match pred {
+                    // Left
+                    0 => {
+                        anyhow::ensure!(block_x > 0, "invalid endpoint prediction");
+                        prev_endpoint
+                    }
+                    // Upper
+                    1 => {
+                        anyhow::ensure!(block_y > 0, "invalid endpoint prediction");
+                        blocks[blocks.len() - num_blocks_x].0
+                    }
+                    // Upper left
+                    2 => {
+                        anyhow::ensure!(block_x > 0 && block_y > 0, "invalid endpoint prediction");
+                        blocks[blocks.len() - num_blocks_x - 1].0
+                    }
+                    _ => {
+                        let delta = reader.read_huffman(&tables.delta_endpoint)? as usize;
+                        match prev_endpoint + delta {
+                            endpoint if endpoint >= num_endpoints => endpoint - num_endpoints,
+                            endpoint => endpoint,
+                        }
+                    }
+                };
+                anyhow::ensure!(endpoint < num_endpoints, "endpoint index out of bounds");
+                prev_endpoint = endpoint;
+
+                let symbol = if selector_rle_count > 0 {
+                    selector_rle_count -= 1;
+                    num_selectors
+                } else {
+                    let symbol = reader.read_huffman(&tables.selector)? as usize;
+                    if symbol == history_rle_symbol {
+                        let run = reader.read_huffman(&tables.selector_history_rle)?;
+                        selector_rle_count = if run == SELECTOR_HISTORY_BUF_RLE_COUNT_TOTAL - 1 {
+                            reader
+                                .read_vlc(7)?
+                                .saturating_add(SELECTOR_HISTORY_BUF_RLE_COUNT_THRESH)
+                        } else {
+                            run + SELECTOR_HISTORY_BUF_RLE_COUNT_THRESH
+                        } as usize;
+                        anyhow::ensure!(
+                            selector_rle_count <= total_blocks,
+                            "selector run exceeds the slice"
+                        );
+                        selector_rle_count -= 1;
+                        num_selectors
+                    } else {
+                        symbol
+                    }
+                };
+
+                let selector = if symbol >= num_selectors {
+                    let index = symbol - num_selectors;
+                    anyhow::ensure!(
+                        index < history.values.len(),
+                        "selector history index out of bounds"
+                    );
+                    let selector = history.values[index] as usize;
+                    history.use_index(index);
+                    selector
+                } else {
+                    if !history.values.is_empty() {
+                        history.add(symbol as u32);
+                    }
+                    symbol
+                };
+                anyhow::ensure!(selector < num_selectors, "selector index out of bounds");
+
+                anyhow::ensure!(!reader.overrun, "truncated slice");
+                blocks.push((endpoint, selector));
+            }
+        }
+
+        Ok(blocks)
+    }
+}
+
+fn decode_endpoints(data: &[u8], count: usize) -> anyhow::Result<Vec<Endpoint>> {
+    let mut reader = BitReader::new(data);
+    let color5_delta = [
+        Huffman::read(&mut reader)?,
+        Huffman::read(&mut reader)?,
+        Huffman::read(&mut reader)?,
+    ];
+    let intensity_delta = Huffman::read(&mut reader)?;
+    let grayscale = reader.read(1) != 0;
+
+    let mut endpoints = Vec::with_capacity(count);
+    let mut prev = Endpoint {
+        color5: [16; 3],
+        intensity: 0,
+    };
+    for _ in 0..count {
+        let mut endpoint = Endpoint::default();
+        let delta = reader.read_huffman(&intensity_delta)?;
+        endpoint.intensity = ((prev.intensity as u32 + delta) & 7) as u8;
+
+        let channels = if grayscale { 1 } else { 3 };
+        for c in 0..channels {
+            let prev = prev.color5[c];
+            let model = if prev <= COLOR5_PAL0_PREV_HI {
+                0
+            } else if prev <= COLOR5_PAL1_PREV_HI {
+                1
+            } else {
+                2
+            };
+            let delta = reader.read_huffman(&color5_delta[model])?;
+            endpoint.color5[c] = ((prev as u32 + delta) & 31) as u8;
+        }
+        if grayscale {
+            endpoint.color5 = [endpoint.color5[0]; 3];
+        }
+
+        endpoints.push(endpoint);
+        prev = endpoint;
+    }
+
+    Ok(endpoints)
+}
+
+fn decode_selectors(data: &[u8], count: usize) -> anyhow::Result<Vec<Selector>> {
+    let mut reader = BitReader::new(data);
+    let global_codebook = reader.read(1) != 0;
+    anyhow::ensure!(
+        !global_codebook,
+        "global selector codebooks are unsupported"
+    );
+    let hybrid_codebook = reader.read(1) != 0;
+    anyhow::ensure!(
+        !hybrid_codebook,
+        "hybrid selector codebooks are unsupported"
+    );
+
+    let raw = reader.read(1) != 0;
+    let mut selectors = Vec::with_capacity(count);
+    if raw {
+        for _ in 0..count {
+            let mut selector = [0; 4];
+            for row in &mut selector {
+                *row = reader.read(8) as u8;
+            }
+            selectors.push(selector);
+        }
+    } else {
+        // Rows are XOR delta coded against the previous selector.
+        let delta = Huffman::read(&mut reader)?;
+        let mut prev = [0; 4];
+        for i in 0..count {
+            let mut selector = [0; 4];
+            for (row, prev) in selector.iter_mut().zip(&prev) {
+                *row = if i == 0 {
+                    reader.read(8) as u8
+                } else {
+                    reader.read_huffman(&delta)? as u8 ^ prev
+                };
+            }
+            selectors.push(selector);
+            prev = selector;
+        }
+    }
+
+    Ok(selectors)
+}
+
+fn decode_tables(data: &[u8]) -> anyhow::Result<Tables> {
+    let mut reader = BitReader::new(data);
+    let endpoint_pred = Huffman::read(&mut reader)?;
+    anyhow::ensure!(
+        !endpoint_pred.symbols.is_empty(),
+        "missing endpoint prediction table"
+    );
+
+    Ok(Tables {
+        endpoint_pred,
+        delta_endpoint: Huffman::read(&mut reader)?,
+        selector: Huffman::read(&mut reader)?,
+        selector_history_rle: Huffman::read(&mut reader)?,
+        selector_history_len: reader.read(13) as usize,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Compare against the reference transcoder output, see `tests/fixtures/README.md`.
+    fn check(ktx2: &[u8], level: usize, expected: &[u8]) {
+        let image = ktx::Image::new(ktx2).unwrap();
+        let pixels = decode_rgba8(&image, level).unwrap();
+        assert_eq!(pixels.len(), expected.len());
+        assert!(pixels == expected, "level {} differs from reference", level);
+    }
+
+    #[test]
+    fn etc1s() {
+        let ktx2 = include_bytes!("../tests/fixtures/etc1s.ktx2");
+        check(
+            ktx2,
+            0,
+            include_bytes!("../tests/fixtures/etc1s_level0.rgba"),
+        );
+        check(
+            ktx2,
+            1,
+            include_bytes!("../tests/fixtures/etc1s_level1.rgba"),
+        );
+    }
+
+    #[test]
+    fn uastc() {
+        let ktx2 = include_bytes!("../tests/fixtures/uastc.ktx2");
+        check(
+            ktx2,
+            0,
+            include_bytes!("../tests/fixtures/uastc_level0.rgba"),
+        );
+    }
+}
@@ -0,0 +1,172 @@
+use byteorder::{LittleEndian, ReadBytesExt};
+
+pub const MAGIC: [u8; 4] = *b"glTF";
+
+const VERSION: u32 = 2;
+const HEADER_LEN: usize = 12;
+const CHUNK_HEADER_LEN: usize = 8;
+
+const CHUNK_JSON: u32 = 0x4E4F_534A;
+const CHUNK_BIN: u32 = 0x004E_4942;
+
+/// Binary glTF container, borrowing the chunks from the file data.
+pub struct Glb<'a> {
+    pub json: &'a [u8],
+    pub bin: Option<&'a [u8]>,
+}
+
+impl<'a> Glb<'a> {
+    pub fn new(data: &'a [u8]) -> anyhow::Result<Self> {
+        anyhow::ensure!(
+            data.len() >= HEADER_LEN,
+            "glb: file too small for header ({} bytes)",
+            data.len()
+        );
+        anyhow::ensure!(data[..4] == MAGIC, "glb: invalid magic");
+
+        let mut rdr = &data[4..HEADER_LEN];
+        let version = rdr.read_u32::<LittleEndian>()?;
+        let length = rdr.read_u32::<LittleEndian>()? as usize;
+
+        anyhow::ensure!(version == VERSION, "glb: unsupported version {}", version);
+        anyhow::ensure!(
+            length <= data.len(),
+            "glb: header length {} exceeds file size {}",
+            length,
+            data.len()
+        );
+
+        let mut json = None;
+        let mut bin = None;
+
+        let mut offset = HEADER_LEN;
+        while offset < length {
+            anyhow::ensure!(
+                offset + CHUNK_HEADER_LEN <= length,
+                "glb: truncated chunk header at offset {}",
+                offset
+            );
+            let mut rdr = &data[offset..offset + CHUNK_HEADER_LEN];
+            let chunk_len = rdr.read_u32::<LittleEndian>()? as usize;
+            let chunk_ty = rdr.read_u32::<LittleEndian>()?;
+
+            let start = offset + CHUNK_HEADER_LEN;
+            let end = start
+                .checked_add(chunk_len)
+                .filter(|&end| end <= length)
+                .ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "glb: chunk at offset {} with length {} exceeds file length {}",
+                        offset,
+                        chunk_len,
+                        length
+                    )
+                })?;
+            let chunk = &data[start..end];
+
+            match chunk_ty {
+                CHUNK_JSON => {
+                    anyhow::ensure!(
+                        json.is_none() && bin.is_none(),
+                        "glb: JSON chunk must be the first chunk"
+                    );
+                    json = Some(chunk);
+                }
+                CHUNK_BIN => {
+                    anyhow::ensure!(json.is_some(), "glb: JSON chunk must be the first chunk");
+                    anyhow::ensure!(bin.is_none(), "glb: multiple BIN chunks");
+                    bin = Some(chunk);
+                }
+                _ => anyhow::bail!(
+                    "glb: unknown chunk type {:#010x} at offset {}",
+                    chunk_ty,
+                    offset
+                ),
+            }
+
+            // chunks are padded to 4 byte boundaries
+            offset = (end + 3) & !3;
+        }
+
+        let json = json.ok_or_else(|| anyhow::anyhow!("glb: missing JSON chunk"))?;
+
+        Ok(Glb { json, bin })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Binary glTF file of the chunks, padded to 4 byte boundaries.
+    fn glb(chunks: &[(u32, &[u8])]) -> Vec<u8> {
+        let mut data = MAGIC.to_vec();
+        data.extend_from_slice(&VERSION.to_le_bytes());
+        data.extend_from_slice(&0u32.to_le_bytes());
+        for &(ty, chunk) in chunks {
+            let padding = (4 - chunk.len() % 4) % 4;
+            data.extend_from_slice(&((chunk.len() + padding) as u32).to_le_bytes());
+            data.extend_from_slice(&ty.to_le_bytes());
+            data.extend_from_slice(chunk);
+            data.resize(data.len() + padding, b' ');
+        }
+        let length = data.len() as u32;
+        data[8..12].copy_from_slice(&length.to_le_bytes());
+        data
+    }
+
+    fn error(data: &[u8]) -> String {
+        match Glb::new(data) {
+            Ok(_) => panic!("malformed file parsed successfully"),
+            Err(err) => err.to_string(),
+        }
+    }
+
+    #[test]
+    fn json_and_bin() {
+        let data = glb(&[(CHUNK_JSON, b"{}"), (CHUNK_BIN, &[1, 2, 3, 4])]);
+        let file = Glb::new(&data).unwrap();
+        assert_eq!(file.json, b"{}  ");
+        assert_eq!(file.bin, Some(&[1, 2, 3, 4][..]));
+
+        let data = glb(&[(CHUNK_JSON, b"{}")]);
+        assert_eq!(Glb::new(&data).unwrap().bin, None);
+    }
+
+    #[test]
+    fn truncated_chunk() {
+        let mut data = glb(&[(CHUNK_JSON, b"{}"), (CHUNK_BIN, &[1, 2, 3, 4])]);
+        data.truncate(data.len() - 2);
+        let length = data.len() as u32;
+        data[8..12].copy_from_slice(&length.to_le_bytes());
+        assert_eq!(
+            error(&data),
+            "glb: chunk at offset 24 with length 4 exceeds file length 34"
+        );
+
+        data.truncate(28);
+        data[8..12].copy_from_slice(&28u32.to_le_bytes());
+        assert_eq!(error(&data), "glb: truncated chunk header at offset 24");
+    }
+
+    #[test]
+    fn header_length_exceeds_file_size() {
+        let data = glb(&[(CHUNK_JSON, b"{}")]);
+        assert_eq!(
+            error(&data[..data.len() - 1]),
+            "glb: header length 24 exceeds file size 23"
+        );
+    }
+
+    #[test]
+    fn json_not_first() {
+        let data = glb(&[(CHUNK_BIN, &[1, 2, 3, 4]), (CHUNK_JSON, b"{}")]);
+        assert_eq!(error(&data), "glb: JSON chunk must be the first chunk");
+
+        let data = glb(&[(CHUNK_JSON, b"{}"), (CHUNK_JSON, b"{}")]);
+        assert_eq!(error(&data), "glb: JSON chunk must be the first chunk");
+
+        let data = glb(&[(CHUNK_BIN, &[1, 2, 3, 4])]);
+        assert_eq!(error(&data), "glb: JSON chunk must be the first chunk");
+    }
+}
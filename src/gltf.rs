@@ -0,0 +1,955 @@
+use crate::glb::{self, Glb};
+use crate::uri;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Document {
+    pub asset: Asset,
+    pub scene: Option<usize>,
+    #[serde(default)]
+    pub scenes: Vec<Scene>,
+    #[serde(default)]
+    pub nodes: Vec<Node>,
+    #[serde(default)]
+    pub buffers: Vec<Buffer>,
+    #[serde(default)]
+    pub buffer_views: Vec<BufferView>,
+    #[serde(default)]
+    pub accessors: Vec<Accessor>,
+    #[serde(default)]
+    pub meshes: Vec<Mesh>,
+    #[serde(default)]
+    pub materials: Vec<Material>,
+    #[serde(default)]
+    pub textures: Vec<Texture>,
+    #[serde(default)]
+    pub images: Vec<Image>,
+    #[serde(default)]
+    pub samplers: Vec<Sampler>,
+    #[serde(default)]
+    pub skins: Vec<Skin>,
+    #[serde(default)]
+    pub animations: Vec<Animation>,
+    #[serde(default)]
+    pub extensions: DocumentExtensions,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct DocumentExtensions {
+    #[serde(rename = "KHR_lights_punctual")]
+    pub lights_punctual: Option<LightsPunctual>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LightsPunctual {
+    pub lights: Vec<Light>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Light {
+    #[serde(default = "default_color")]
+    pub color: [f32; 3],
+    #[serde(default = "default_one")]
+    pub intensity: f32,
+    #[serde(rename = "type")]
+    pub ty: LightType,
+    pub range: Option<f32>,
+    pub spot: Option<Spot>,
+}
+
+fn default_color() -> [f32; 3] {
+    [1.0; 3]
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LightType {
+    Directional,
+    Point,
+    Spot,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Spot {
+    pub inner_cone_angle: f32,
+    pub outer_cone_angle: f32,
+}
+
+impl Default for Spot {
+    fn default() -> Self {
+        Spot {
+            inner_cone_angle: 0.0,
+            outer_cone_angle: std::f32::consts::FRAC_PI_4,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Asset {
+    pub version: String,
+    pub min_version: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Scene {
+    #[serde(default)]
+    pub nodes: Vec<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Node {
+    #[serde(default)]
+    pub children: Vec<usize>,
+    pub mesh: Option<usize>,
+    pub skin: Option<usize>,
+    pub matrix: Option<[f32; 16]>,
+    pub translation: Option<[f32; 3]>,
+    pub rotation: Option<[f32; 4]>,
+    pub scale: Option<[f32; 3]>,
+    #[serde(default)]
+    pub weights: Vec<f32>,
+    #[serde(default)]
+    pub extensions: NodeExtensions,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct NodeExtensions {
+    #[serde(rename = "KHR_lights_punctual")]
+    pub light: Option<NodeLight>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NodeLight {
+    pub light: usize,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Skin {
+    pub inverse_bind_matrices: Option<usize>,
+    pub skeleton: Option<usize>,
+    pub joints: Vec<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Animation {
+    pub channels: Vec<Channel>,
+    pub samplers: Vec<AnimationSampler>,
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Channel {
+    pub sampler: usize,
+    pub target: Target,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Target {
+    pub node: Option<usize>,
+    pub path: TargetPath,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TargetPath {
+    Translation,
+    Rotation,
+    Scale,
+    Weights,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AnimationSampler {
+    pub input: usize,
+    #[serde(default)]
+    pub interpolation: Interpolation,
+    pub output: usize,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
+pub enum Interpolation {
+    #[serde(rename = "LINEAR")]
+    Linear,
+    #[serde(rename = "STEP")]
+    Step,
+    #[serde(rename = "CUBICSPLINE")]
+    CubicSpline,
+}
+
+impl Default for Interpolation {
+    fn default() -> Self {
+        Interpolation::Linear
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Buffer {
+    pub uri: Option<String>,
+    pub byte_length: usize,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BufferView {
+    pub buffer: usize,
+    #[serde(default)]
+    pub byte_offset: usize,
+    pub byte_length: usize,
+    pub byte_stride: Option<usize>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "u32")]
+pub enum ComponentType {
+    I8,
+    U8,
+    I16,
+    U16,
+    U32,
+    F32,
+}
+
+impl TryFrom<u32> for ComponentType {
+    type Error = String;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        match value {
+            5120 => Ok(ComponentType::I8),
+            5121 => Ok(ComponentType::U8),
+            5122 => Ok(ComponentType::I16),
+            5123 => Ok(ComponentType::U16),
+            5125 => Ok(ComponentType::U32),
+            5126 => Ok(ComponentType::F32),
+            _ => Err(format!("invalid accessor component type {}", value)),
+        }
+    }
+}
+
+impl ComponentType {
+    pub fn size(self) -> usize {
+        match self {
+            ComponentType::I8 | ComponentType::U8 => 1,
+            ComponentType::I16 | ComponentType::U16 => 2,
+            ComponentType::U32 | ComponentType::F32 => 4,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
+pub enum AccessorType {
+    #[serde(rename = "SCALAR")]
+    Scalar,
+    #[serde(rename = "VEC2")]
+    Vec2,
+    #[serde(rename = "VEC3")]
+    Vec3,
+    #[serde(rename = "VEC4")]
+    Vec4,
+    #[serde(rename = "MAT2")]
+    Mat2,
+    #[serde(rename = "MAT3")]
+    Mat3,
+    #[serde(rename = "MAT4")]
+    Mat4,
+}
+
+impl AccessorType {
+    pub fn num_components(self) -> usize {
+        match self {
+            AccessorType::Scalar => 1,
+            AccessorType::Vec2 => 2,
+            AccessorType::Vec3 => 3,
+            AccessorType::Vec4 | AccessorType::Mat2 => 4,
+            AccessorType::Mat3 => 9,
+            AccessorType::Mat4 => 16,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Accessor {
+    pub buffer_view: Option<usize>,
+    #[serde(default)]
+    pub byte_offset: usize,
+    pub component_type: ComponentType,
+    #[serde(default)]
+    pub normalized: bool,
+    pub count: usize,
+    #[serde(rename = "type")]
+    pub ty: AccessorType,
+    pub min: Option<Vec<f32>>,
+    pub max: Option<Vec<f32>>,
+    pub sparse: Option<Sparse>,
+}
+
+impl Accessor {
+    /// Size of a single tightly packed element in bytes.
+    pub fn element_size(&self) -> usize {
+        self.component_type.size() * self.ty.num_components()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Sparse {
+    pub count: usize,
+    pub indices: SparseIndices,
+    pub values: SparseValues,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SparseIndices {
+    pub buffer_view: usize,
+    #[serde(default)]
+    pub byte_offset: usize,
+    pub component_type: ComponentType,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SparseValues {
+    pub buffer_view: usize,
+    #[serde(default)]
+    pub byte_offset: usize,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Mesh {
+    pub primitives: Vec<Primitive>,
+    #[serde(default)]
+    pub weights: Vec<f32>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "u32")]
+pub enum Mode {
+    Points,
+    Lines,
+    LineLoop,
+    LineStrip,
+    Triangles,
+    TriangleStrip,
+    TriangleFan,
+}
+
+impl Default for Mode {
+    fn default() -> Self {
+        Mode::Triangles
+    }
+}
+
+impl TryFrom<u32> for Mode {
+    type Error = String;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Mode::Points),
+            1 => Ok(Mode::Lines),
+            2 => Ok(Mode::LineLoop),
+            3 => Ok(Mode::LineStrip),
+            4 => Ok(Mode::Triangles),
+            5 => Ok(Mode::TriangleStrip),
+            6 => Ok(Mode::TriangleFan),
+            _ => Err(format!("invalid primitive mode {}", value)),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Primitive {
+    pub attributes: HashMap<String, usize>,
+    pub indices: Option<usize>,
+    pub material: Option<usize>,
+    #[serde(default)]
+    pub mode: Mode,
+    #[serde(default)]
+    pub targets: Vec<HashMap<String, usize>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TextureInfo {
+    pub index: usize,
+    #[serde(default)]
+    pub tex_coord: usize,
+    #[serde(default)]
+    pub extensions: TextureInfoExtensions,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NormalTextureInfo {
+    pub index: usize,
+    #[serde(default)]
+    pub tex_coord: usize,
+    #[serde(default)]
+    pub extensions: TextureInfoExtensions,
+    #[serde(default = "default_one")]
+    pub scale: f32,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OcclusionTextureInfo {
+    pub index: usize,
+    #[serde(default)]
+    pub tex_coord: usize,
+    #[serde(default)]
+    pub extensions: TextureInfoExtensions,
+    #[serde(default = "default_one")]
+    pub strength: f32,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct TextureInfoExtensions {
+    #[serde(rename = "KHR_texture_transform")]
+    pub texture_transform: Option<TextureTransform>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TextureTransform {
+    pub offset: [f32; 2],
+    pub rotation: f32,
+    pub scale: [f32; 2],
+    pub tex_coord: Option<usize>,
+}
+
+impl Default for TextureTransform {
+    fn default() -> Self {
+        TextureTransform {
+            offset: [0.0; 2],
+            rotation: 0.0,
+            scale: [1.0; 2],
+            tex_coord: None,
+        }
+    }
+}
+
+fn default_one() -> f32 {
+    1.0
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PbrMetallicRoughness {
+    pub base_color_factor: [f32; 4],
+    pub base_color_texture: Option<TextureInfo>,
+    pub metallic_factor: f32,
+    pub roughness_factor: f32,
+    pub metallic_roughness_texture: Option<TextureInfo>,
+}
+
+impl Default for PbrMetallicRoughness {
+    fn default() -> Self {
+        PbrMetallicRoughness {
+            base_color_factor: [1.0; 4],
+            base_color_texture: None,
+            metallic_factor: 1.0,
+            roughness_factor: 1.0,
+            metallic_roughness_texture: None,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
+pub enum AlphaMode {
+    #[serde(rename = "OPAQUE")]
+    Opaque,
+    #[serde(rename = "MASK")]
+    Mask,
+    #[serde(rename = "BLEND")]
+    Blend,
+}
+
+impl Default for AlphaMode {
+    fn default() -> Self {
+        AlphaMode::Opaque
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Material {
+    pub pbr_metallic_roughness: PbrMetallicRoughness,
+    pub normal_texture: Option<NormalTextureInfo>,
+    pub occlusion_texture: Option<OcclusionTextureInfo>,
+    pub emissive_texture: Option<TextureInfo>,
+    pub emissive_factor: [f32; 3],
+    pub alpha_mode: AlphaMode,
+    pub alpha_cutoff: f32,
+    pub double_sided: bool,
+    pub extensions: MaterialExtensions,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Material {
+            pbr_metallic_roughness: PbrMetallicRoughness::default(),
+            normal_texture: None,
+            occlusion_texture: None,
+            emissive_texture: None,
+            emissive_factor: [0.0; 3],
+            alpha_mode: AlphaMode::Opaque,
+            alpha_cutoff: 0.5,
+            double_sided: false,
+            extensions: MaterialExtensions::default(),
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct MaterialExtensions {
+    #[serde(rename = "KHR_materials_emissive_strength")]
+    pub emissive_strength: Option<EmissiveStrength>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EmissiveStrength {
+    #[serde(default = "default_one")]
+    pub emissive_strength: f32,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Texture {
+    pub source: Option<usize>,
+    pub sampler: Option<usize>,
+    #[serde(default)]
+    pub extensions: TextureExtensions,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct TextureExtensions {
+    #[serde(rename = "KHR_texture_basisu")]
+    pub texture_basisu: Option<TextureBasisu>,
+}
+
+/// KTX2 image with Basis Universal payload.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TextureBasisu {
+    pub source: usize,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Image {
+    pub uri: Option<String>,
+    pub buffer_view: Option<usize>,
+    pub mime_type: Option<String>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "u32")]
+pub enum Filter {
+    Nearest,
+    Linear,
+    NearestMipmapNearest,
+    LinearMipmapNearest,
+    NearestMipmapLinear,
+    LinearMipmapLinear,
+}
+
+impl TryFrom<u32> for Filter {
+    type Error = String;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        match value {
+            9728 => Ok(Filter::Nearest),
+            9729 => Ok(Filter::Linear),
+            9984 => Ok(Filter::NearestMipmapNearest),
+            9985 => Ok(Filter::LinearMipmapNearest),
+            9986 => Ok(Filter::NearestMipmapLinear),
+            9987 => Ok(Filter::LinearMipmapLinear),
+            _ => Err(format!("invalid sampler filter {}", value)),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "u32")]
+pub enum Wrap {
+    ClampToEdge,
+    MirroredRepeat,
+    Repeat,
+}
+
+impl Default for Wrap {
+    fn default() -> Self {
+        Wrap::Repeat
+    }
+}
+
+impl TryFrom<u32> for Wrap {
+    type Error = String;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        match value {
+            33071 => Ok(Wrap::ClampToEdge),
+            33648 => Ok(Wrap::MirroredRepeat),
+            10497 => Ok(Wrap::Repeat),
+            _ => Err(format!("invalid sampler wrap mode {}", value)),
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Sampler {
+    pub mag_filter: Option<Filter>,
+    pub min_filter: Option<Filter>,
+    pub wrap_s: Wrap,
+    pub wrap_t: Wrap,
+}
+
+impl Document {
+    /// Check all references between objects of the document.
+    ///
+    /// Afterwards indices into the document arrays can be used without bounds checks
+    /// and the node hierarchy is guaranteed to be a forest.
+    fn validate(&self) -> anyhow::Result<()> {
+        fn check(index: usize, len: usize, what: &str) -> anyhow::Result<()> {
+            anyhow::ensure!(
+                index < len,
+                "{} index {} out of bounds ({})",
+                what,
+                index,
+                len
+            );
+            Ok(())
+        }
+
+        let num_nodes = self.nodes.len();
+        let num_accessors = self.accessors.len();
+        let num_textures = self.textures.len();
+        let num_lights = self
+            .extensions
+            .lights_punctual
+            .as_ref()
+            .map_or(0, |lights| lights.lights.len());
+
+        if let Some(scene) = self.scene {
+            check(scene, self.scenes.len(), "scene")?;
+        }
+
+        let mut parents = vec![None; num_nodes];
+        for (i, node) in self.nodes.iter().enumerate() {
+            for &child in &node.children {
+                check(child, num_nodes, "node")?;
+                anyhow::ensure!(
+                    parents[child].replace(i).is_none(),
+                    "node {} has multiple parents",
+                    child
+                );
+            }
+            if let Some(mesh) = node.mesh {
+                check(mesh, self.meshes.len(), "mesh")?;
+            }
+            if let Some(skin) = node.skin {
+                check(skin, self.skins.len(), "skin")?;
+            }
+            if let Some(ref light) = node.extensions.light {
+                check(light.light, num_lights, "light")?;
+            }
+        }
+        // With a single parent per node, walking up more than `num_nodes` steps implies a cycle.
+        for i in 0..num_nodes {
+            let mut node = i;
+            let mut depth = 0;
+            while let Some(parent) = parents[node] {
+                anyhow::ensure!(depth < num_nodes, "node {} is part of a cycle", i);
+                node = parent;
+                depth += 1;
+            }
+        }
+        for scene in &self.scenes {
+            for &node in &scene.nodes {
+                check(node, num_nodes, "node")?;
+                anyhow::ensure!(
+                    parents[node].is_none(),
+                    "scene root {} is a child node",
+                    node
+                );
+            }
+        }
+
+        for skin in &self.skins {
+            if let Some(accessor) = skin.inverse_bind_matrices {
+                check(accessor, num_accessors, "accessor")?;
+            }
+            if let Some(skeleton) = skin.skeleton {
+                check(skeleton, num_nodes, "node")?;
+            }
+            for &joint in &skin.joints {
+                check(joint, num_nodes, "node")?;
+            }
+        }
+
+        for animation in &self.animations {
+            for channel in &animation.channels {
+                check(
+                    channel.sampler,
+                    animation.samplers.len(),
+                    "animation sampler",
+                )?;
+                if let Some(node) = channel.target.node {
+                    check(node, num_nodes, "node")?;
+                }
+            }
+            for sampler in &animation.samplers {
+                check(sampler.input, num_accessors, "accessor")?;
+                check(sampler.output, num_accessors, "accessor")?;
+            }
+        }
+
+        for (i, view) in self.buffer_views.iter().enumerate() {
+            check(view.buffer, self.buffers.len(), "buffer")?;
+            let end = view.byte_offset.checked_add(view.byte_length);
+            anyhow::ensure!(
+                end.map_or(false, |end| end <= self.buffers[view.buffer].byte_length),
+                "buffer view {} out of buffer bounds",
+                i
+            );
+        }
+
+        for accessor in &self.accessors {
+            if let Some(view) = accessor.buffer_view {
+                check(view, self.buffer_views.len(), "buffer view")?;
+            }
+            if let Some(ref sparse) = accessor.sparse {
+                check(
+                    sparse.indices.buffer_view,
+                    self.buffer_views.len(),
+                    "buffer view",
+                )?;
+                check(
+                    sparse.values.buffer_view,
+                    self.buffer_views.len(),
+                    "buffer view",
+                )?;
+            }
+        }
+
+        for mesh in &self.meshes {
+            for primitive in &mesh.primitives {
+                let attributes = primitive.attributes.values();
+                let targets = primitive.targets.iter().flat_map(|target| target.values());
+                for &accessor in attributes.chain(targets).chain(&primitive.indices) {
+                    check(accessor, num_accessors, "accessor")?;
+                }
+                if let Some(material) = primitive.material {
+                    check(material, self.materials.len(), "material")?;
+                }
+            }
+        }
+
+        for material in &self.materials {
+            let pbr = &material.pbr_metallic_roughness;
+            let textures = [
+                pbr.base_color_texture.as_ref().map(|info| info.index),
+                pbr.metallic_roughness_texture
+                    .as_ref()
+                    .map(|info| info.index),
+                material.normal_texture.as_ref().map(|info| info.index),
+                material.occlusion_texture.as_ref().map(|info| info.index),
+                material.emissive_texture.as_ref().map(|info| info.index),
+            ];
+            for &texture in textures.iter().flatten() {
+                check(texture, num_textures, "texture")?;
+            }
+        }
+
+        for texture in &self.textures {
+            let basisu = texture.extensions.texture_basisu.as_ref();
+            for &image in texture
+                .source
+                .iter()
+                .chain(basisu.map(|basisu| &basisu.source))
+            {
+                check(image, self.images.len(), "image")?;
+            }
+            if let Some(sampler) = texture.sampler {
+                check(sampler, self.samplers.len(), "sampler")?;
+            }
+        }
+
+        for image in &self.images {
+            if let Some(view) = image.buffer_view {
+                check(view, self.buffer_views.len(), "buffer view")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// glTF document together with the loaded buffer data.
+pub struct Gltf {
+    pub document: Document,
+    pub buffers: Vec<Vec<u8>>,
+    pub directory: PathBuf,
+}
+
+impl Gltf {
+    /// Load a glTF asset from either a `.gltf` JSON or a binary `.glb` file.
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        let directory = path.parent().unwrap_or_else(|| Path::new("")).to_path_buf();
+        let data = fs::read(path)?;
+
+        if data.starts_with(&glb::MAGIC) {
+            let glb = Glb::new(&data)?;
+            Self::load(glb.json, glb.bin, directory)
+        } else {
+            Self::load(&data, None, directory)
+        }
+    }
+
+    fn load(json: &[u8], bin: Option<&[u8]>, directory: PathBuf) -> anyhow::Result<Self> {
+        let document: Document = serde_json::from_slice(json)?;
+        document.validate()?;
+
+        let buffers = document
+            .buffers
+            .iter()
+            .enumerate()
+            .map(|(i, buffer)| {
+                let data = match buffer.uri {
+                    Some(ref uri) => uri::resolve(&directory, uri)?,
+                    // Only the first buffer may refer to the GLB-stored BIN chunk.
+                    None if i == 0 => bin
+                        .ok_or_else(|| anyhow::anyhow!("buffer {} without uri", i))?
+                        .to_vec(),
+                    None => anyhow::bail!("buffer {} without uri", i),
+                };
+                anyhow::ensure!(
+                    data.len() >= buffer.byte_length,
+                    "buffer {} is smaller than its byte length",
+                    i
+                );
+                Ok(data)
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        Ok(Gltf {
+            document,
+            buffers,
+            directory,
+        })
+    }
+
+    /// Data of the buffer view, bounds checked against the underlying buffer.
+    pub fn buffer_view(&self, view: usize) -> anyhow::Result<&[u8]> {
+        let desc = self
+            .document
+            .buffer_views
+            .get(view)
+            .ok_or_else(|| anyhow::anyhow!("buffer view {} out of bounds", view))?;
+        let end = desc.byte_offset.checked_add(desc.byte_length);
+        self.buffers
+            .get(desc.buffer)
+            .zip(end)
+            .and_then(|(buffer, end)| buffer.get(desc.byte_offset..end))
+            .ok_or_else(|| anyhow::anyhow!("buffer view {} out of buffer bounds", view))
+    }
+
+    /// Image data of the texture, preferring the `KHR_texture_basisu` source if present.
+    pub fn texture_image_data(&self, texture: usize) -> anyhow::Result<Vec<u8>> {
+        let desc = &self.document.textures[texture];
+        let image = desc
+            .extensions
+            .texture_basisu
+            .as_ref()
+            .map(|basisu| basisu.source)
+            .or(desc.source)
+            .ok_or_else(|| anyhow::anyhow!("texture {} without source image", texture))?;
+        self.image_data(image)
+    }
+
+    pub fn image_data(&self, image: usize) -> anyhow::Result<Vec<u8>> {
+        let desc = &self.document.images[image];
+        match (&desc.uri, desc.buffer_view) {
+            (Some(uri), _) => uri::resolve(&self.directory, uri),
+            (None, Some(view)) => Ok(self.buffer_view(view)?.to_vec()),
+            (None, None) => anyhow::bail!("image {} without uri or buffer view", image),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(json: &str) -> anyhow::Result<Gltf> {
+        Gltf::load(json.as_bytes(), None, PathBuf::new())
+    }
+
+    #[test]
+    fn index_out_of_bounds() {
+        let err = load(r#"{"asset":{"version":"2.0"},"nodes":[{"mesh":0}]}"#)
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("mesh index 0 out of bounds"));
+    }
+
+    #[test]
+    fn buffer_view_out_of_bounds() {
+        let json = r#"{
+            "asset": {"version": "2.0"},
+            "buffers": [{"uri": "data:application/octet-stream;base64,AAAAAA==", "byteLength": 4}],
+            "bufferViews": [{"buffer": 0, "byteOffset": 2, "byteLength": 18446744073709551615}]
+        }"#;
+        let err = load(json).err().unwrap();
+        assert!(err
+            .to_string()
+            .contains("buffer view 0 out of buffer bounds"));
+    }
+
+    #[test]
+    fn node_cycle() {
+        let json = r#"{
+            "asset": {"version": "2.0"},
+            "nodes": [{"children": [1]}, {"children": [0]}]
+        }"#;
+        let err = load(json).err().unwrap();
+        assert!(err.to_string().contains("cycle"));
+    }
+
+    #[test]
+    fn multiple_parents() {
+        let json = r#"{
+            "asset": {"version": "2.0"},
+            "nodes": [{"children": [2]}, {"children": [2]}, {}]
+        }"#;
+        let err = load(json).err().unwrap();
+        assert!(err.to_string().contains("node 2 has multiple parents"));
+    }
+}
@@ -1,10 +1,15 @@
 use byteorder::{LittleEndian, ReadBytesExt};
 use std::io::Read;
 
-const FILE_IDENTIFIER: [u8; 12] = [
+pub const FILE_IDENTIFIER: [u8; 12] = [
     0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
 ];
 
+const SUPERCOMPRESSION_NONE: u32 = 0;
+pub const SUPERCOMPRESSION_BASIS_LZ: u32 = 1;
+const SUPERCOMPRESSION_ZSTD: u32 = 2;
+const SUPERCOMPRESSION_ZLIB: u32 = 3;
+
 #[derive(Debug, Copy, Clone)]
 pub struct Header {
     pub format: u32,
@@ -18,17 +23,204 @@ pub struct Header {
     pub supercompression_scheme: u32,
 }
 
+// Data format descriptor color models
+pub const COLOR_MODEL_RGBSDA: u8 = 1;
+pub const COLOR_MODEL_ETC1S: u8 = 163;
+pub const COLOR_MODEL_UASTC: u8 = 166;
+
+const TRANSFER_SRGB: u8 = 2;
+const FLAG_ALPHA_PREMULTIPLIED: u8 = 1;
+
+// Sample channel ids
+pub const CHANNEL_RGBSDA_ALPHA: u8 = 15;
+pub const CHANNEL_UASTC_RGBA: u8 = 3;
+pub const CHANNEL_UASTC_RRRG: u8 = 5;
+
+// Sample qualifiers
+pub const QUALIFIER_FLOAT: u8 = 0x8;
+
+/// Khronos basic data format descriptor block.
+#[derive(Debug, Clone)]
+pub struct DataFormatDescriptor {
+    pub color_model: u8,
+    pub color_primaries: u8,
+    pub transfer_function: u8,
+    pub flags: u8,
+    /// Texel block size in each dimension.
+    pub texel_block_dimensions: [u32; 4],
+    pub bytes_plane: [u8; 8],
+    pub samples: Vec<Sample>,
+}
+
+/// Bit range of a single channel inside a texel block.
+#[derive(Debug, Copy, Clone)]
+pub struct Sample {
+    pub bit_offset: u16,
+    pub bit_length: u32,
+    pub channel_type: u8,
+    /// Linear, exponent, signed and float flags in the upper bits of the channel type.
+    pub qualifiers: u8,
+    pub position: [u8; 4],
+    pub lower: u32,
+    pub upper: u32,
+}
+
+impl DataFormatDescriptor {
+    fn new(data: &[u8]) -> Result<Self, KtxError> {
+        let truncated = |_| KtxError::BadDataFormatDescriptor("truncated data format descriptor");
+
+        let mut rdr = std::io::Cursor::new(data);
+        let total_len = rdr.read_u32::<LittleEndian>().map_err(truncated)? as u64;
+        while rdr.position() < total_len {
+            let start = rdr.position();
+            let header = rdr.read_u32::<LittleEndian>().map_err(truncated)?;
+            let vendor = header & 0x1FFFF;
+            let ty = header >> 17;
+            let block_len = rdr.read_u32::<LittleEndian>().map_err(truncated)? >> 16;
+            if block_len < 8 {
+                return Err(KtxError::BadDataFormatDescriptor(
+                    "data format descriptor block too small",
+                ));
+            }
+
+            if vendor != 0 || ty != 0 {
+                rdr.set_position(start + block_len as u64);
+                continue;
+            }
+
+            let mut fields = [0; 4];
+            let mut texel_block_dimensions = [0; 4];
+            let mut bytes_plane = [0; 8];
+            rdr.read_exact(&mut fields).map_err(truncated)?;
+            rdr.read_exact(&mut texel_block_dimensions)
+                .map_err(truncated)?;
+            rdr.read_exact(&mut bytes_plane).map_err(truncated)?;
+            let [color_model, color_primaries, transfer_function, flags] = fields;
+
+            let num_samples = (block_len as usize).saturating_sub(24) / 16;
+            let samples = (0..num_samples)
+                .map(|_| {
+                    let bits = rdr.read_u32::<LittleEndian>()?;
+                    let mut position = [0; 4];
+                    rdr.read_exact(&mut position)?;
+                    let channel = (bits >> 24) as u8;
+                    Ok(Sample {
+                        bit_offset: bits as u16,
+                        bit_length: ((bits >> 16) & 0xFF) + 1,
+                        channel_type: channel & 0xF,
+                        qualifiers: channel >> 4,
+                        position,
+                        lower: rdr.read_u32::<LittleEndian>()?,
+                        upper: rdr.read_u32::<LittleEndian>()?,
+                    })
+                })
+                .collect::<std::io::Result<Vec<_>>>()
+                .map_err(truncated)?;
+
+            return Ok(DataFormatDescriptor {
+                color_model,
+                color_primaries,
+                transfer_function,
+                flags,
+                texel_block_dimensions: [
+                    texel_block_dimensions[0] as u32 + 1,
+                    texel_block_dimensions[1] as u32 + 1,
+                    texel_block_dimensions[2] as u32 + 1,
+                    texel_block_dimensions[3] as u32 + 1,
+                ],
+                bytes_plane,
+                samples,
+            });
+        }
+
+        Err(KtxError::BadDataFormatDescriptor(
+            "missing basic data format descriptor",
+        ))
+    }
+
+    pub fn is_srgb(&self) -> bool {
+        self.transfer_function == TRANSFER_SRGB
+    }
+
+    pub fn is_premultiplied(&self) -> bool {
+        self.flags & FLAG_ALPHA_PREMULTIPLIED != 0
+    }
+}
+
 pub struct Image {
     pub header: Header,
+    pub dfd: DataFormatDescriptor,
     pub levels: Vec<Vec<u8>>,
+    /// Supercompression global data, only used by BasisLZ.
+    pub global_data: Vec<u8>,
+}
+
+/// Errors caused by malformed or unsupported KTX2 files.
+#[derive(Debug)]
+pub enum KtxError {
+    BadMagic,
+    TruncatedHeader,
+    LevelOutOfBounds {
+        level: usize,
+    },
+    /// Decompressed level size differs from `uncompressedByteLength`.
+    /// Decompression stops right after exceeding it, `actual` is at most `expected + 1`.
+    LevelSizeMismatch {
+        level: usize,
+        expected: u64,
+        actual: u64,
+    },
+    UnsupportedScheme(u32),
+    BadIndex(&'static str),
+    BadDataFormatDescriptor(&'static str),
 }
 
+impl std::fmt::Display for KtxError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            KtxError::BadMagic => write!(f, "not a KTX2 file"),
+            KtxError::TruncatedHeader => write!(f, "truncated KTX2 header"),
+            KtxError::LevelOutOfBounds { level } => {
+                write!(f, "level {} exceeds the file size", level)
+            }
+            KtxError::LevelSizeMismatch {
+                level,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "level {} decompressed to {} bytes, expected {}",
+                level, actual, expected
+            ),
+            KtxError::UnsupportedScheme(scheme) => {
+                write!(f, "unsupported supercompression scheme {}", scheme)
+            }
+            KtxError::BadIndex(msg) => write!(f, "bad KTX2 index: {}", msg),
+            KtxError::BadDataFormatDescriptor(msg) => write!(f, "bad KTX2 DFD: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for KtxError {}
+
+/// Size of the file header and index, excluding the level index.
+const HEADER_LEN: u64 = 80;
+/// Size of a single entry in the level index.
+const LEVEL_INDEX_LEN: u64 = 24;
+
 impl Image {
     pub fn new(data: &[u8]) -> anyhow::Result<Self> {
+        // Check the available prefix to tell foreign files apart from truncated ones.
+        let identifier_len = data.len().min(FILE_IDENTIFIER.len());
+        if data[..identifier_len] != FILE_IDENTIFIER[..identifier_len] {
+            return Err(KtxError::BadMagic.into());
+        }
+        if (data.len() as u64) < HEADER_LEN {
+            return Err(KtxError::TruncatedHeader.into());
+        }
+
         let mut rdr = std::io::Cursor::new(data);
-        let mut identifier = [0; 12];
-        rdr.read_exact(&mut identifier)?;
-        assert_eq!(identifier, FILE_IDENTIFIER);
+        rdr.set_position(FILE_IDENTIFIER.len() as u64);
 
         let header = Header {
             format: rdr.read_u32::<LittleEndian>()?,
@@ -42,27 +234,321 @@ impl Image {
             supercompression_scheme: rdr.read_u32::<LittleEndian>()?,
         };
 
-        let _dfd_offset = rdr.read_u32::<LittleEndian>()?;
-        let _dfd_len = rdr.read_u32::<LittleEndian>()?;
+        match header.supercompression_scheme {
+            SUPERCOMPRESSION_NONE
+            | SUPERCOMPRESSION_BASIS_LZ
+            | SUPERCOMPRESSION_ZSTD
+            | SUPERCOMPRESSION_ZLIB => (),
+            scheme => return Err(KtxError::UnsupportedScheme(scheme).into()),
+        }
+
+        let dfd_offset = rdr.read_u32::<LittleEndian>()?;
+        let dfd_len = rdr.read_u32::<LittleEndian>()?;
 
         let _kvd_offset = rdr.read_u32::<LittleEndian>()?;
         let _kvd_len = rdr.read_u32::<LittleEndian>()?;
 
-        let _sgd_offset = rdr.read_u64::<LittleEndian>()?;
-        let _sgd_len = rdr.read_u64::<LittleEndian>()?;
+        let sgd_offset = rdr.read_u64::<LittleEndian>()?;
+        let sgd_len = rdr.read_u64::<LittleEndian>()?;
 
-        let levels = (0..header.level_count.max(1))
-            .map(|_| {
+        let level_count = header.level_count.max(1);
+        if (data.len() as u64) < HEADER_LEN + LEVEL_INDEX_LEN * level_count as u64 {
+            return Err(KtxError::TruncatedHeader.into());
+        }
+
+        let levels = (0..level_count as usize)
+            .map(|level| {
                 let offset = rdr.read_u64::<LittleEndian>()?;
                 let len = rdr.read_u64::<LittleEndian>()?;
                 let len_uncompressed = rdr.read_u64::<LittleEndian>()?;
 
-                assert_eq!(len, len_uncompressed); // TODO
-
-                Ok(data[offset as usize..(offset + len) as usize].to_vec())
+                let data = range(data, offset, len).ok_or(KtxError::LevelOutOfBounds { level })?;
+                decompress(
+                    header.supercompression_scheme,
+                    level,
+                    data,
+                    len_uncompressed,
+                )
             })
             .collect::<anyhow::Result<Vec<_>>>()?;
 
-        Ok(Image { header, levels })
+        let dfd = range(data, dfd_offset as u64, dfd_len as u64)
+            .ok_or(KtxError::BadIndex("data format descriptor out of bounds"))?;
+        let dfd = DataFormatDescriptor::new(dfd)?;
+
+        let global_data = range(data, sgd_offset, sgd_len)
+            .ok_or(KtxError::BadIndex(
+                "supercompression global data out of bounds",
+            ))?
+            .to_vec();
+
+        Ok(Image {
+            header,
+            dfd,
+            levels,
+            global_data,
+        })
+    }
+}
+
+/// Bounds checked subslice for offsets read from the file.
+fn range(data: &[u8], offset: u64, len: u64) -> Option<&[u8]> {
+    let end = offset.checked_add(len)?;
+    if end > data.len() as u64 {
+        return None;
+    }
+    Some(&data[offset as usize..end as usize])
+}
+
+/// Undo the supercompression of a single mip level.
+fn decompress(
+    scheme: u32,
+    level_index: usize,
+    data: &[u8],
+    len_uncompressed: u64,
+) -> anyhow::Result<Vec<u8>> {
+    // Decoding stops right after exceeding the expected size to bound allocations.
+    let limit = len_uncompressed.saturating_add(1);
+    let mut level = Vec::new();
+    match scheme {
+        SUPERCOMPRESSION_NONE => level.extend_from_slice(data),
+        // BasisLZ levels are decoded by the transcoder, `uncompressedByteLength` is 0.
+        SUPERCOMPRESSION_BASIS_LZ => return Ok(data.to_vec()),
+        SUPERCOMPRESSION_ZSTD => {
+            let mut src = data;
+            let decoder =
+                ruzstd::StreamingDecoder::new(&mut src).map_err(|err| anyhow::anyhow!(err))?;
+            decoder.take(limit).read_to_end(&mut level)?;
+        }
+        SUPERCOMPRESSION_ZLIB => {
+            flate2::read::ZlibDecoder::new(data)
+                .take(limit)
+                .read_to_end(&mut level)?;
+        }
+        _ => return Err(KtxError::UnsupportedScheme(scheme).into()),
+    }
+
+    if level.len() as u64 != len_uncompressed {
+        return Err(KtxError::LevelSizeMismatch {
+            level: level_index,
+            expected: len_uncompressed,
+            actual: level.len() as u64,
+        }
+        .into());
+    }
+    Ok(level)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DFD_OFFSET: usize = 104;
+    const DFD_LEN: usize = 44;
+    const LEVEL_OFFSET: usize = DFD_OFFSET + DFD_LEN;
+
+    /// Uncompressed 1x1 RGBA8 image with a single level.
+    fn file() -> Vec<u8> {
+        let mut data = FILE_IDENTIFIER.to_vec();
+        let u32s = |data: &mut Vec<u8>, values: &[u32]| {
+            for value in values {
+                data.extend_from_slice(&value.to_le_bytes());
+            }
+        };
+        let u64s = |data: &mut Vec<u8>, values: &[u64]| {
+            for value in values {
+                data.extend_from_slice(&value.to_le_bytes());
+            }
+        };
+        // Header and index
+        u32s(&mut data, &[37, 1, 1, 1, 0, 0, 1, 1, SUPERCOMPRESSION_NONE]);
+        u32s(&mut data, &[DFD_OFFSET as u32, DFD_LEN as u32, 0, 0]);
+        u64s(&mut data, &[0, 0]);
+        // Level index
+        u64s(&mut data, &[LEVEL_OFFSET as u64, 4, 4]);
+        // Basic data format descriptor with a single alpha sample
+        u32s(&mut data, &[DFD_LEN as u32, 0, 2 | 40 << 16]);
+        data.extend_from_slice(&[COLOR_MODEL_RGBSDA, 1, TRANSFER_SRGB, 0]);
+        data.extend_from_slice(&[0; 12]);
+        let sample = 24 | 7 << 16 | (CHANNEL_RGBSDA_ALPHA as u32) << 24;
+        u32s(&mut data, &[sample, 0, 0, 255]);
+        // Level data
+        data.extend_from_slice(&[1, 2, 3, 4]);
+        data
+    }
+
+    fn set_u32(data: &mut [u8], offset: usize, value: u32) {
+        data[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    }
+
+    fn set_u64(data: &mut [u8], offset: usize, value: u64) {
+        data[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+    }
+
+    /// Hand-made zstd frame with a single raw block holding `[1, 2, 3, 4]`.
+    const ZSTD_LEVEL: &[u8] = &[
+        0x28, 0xB5, 0x2F, 0xFD, // magic
+        0x20, 4, // single segment, content size
+        0x21, 0, 0, // last raw block of 4 bytes
+        1, 2, 3, 4, // data
+    ];
+
+    /// Hand-made zlib stream with a single stored block holding `[1, 2, 3, 4]`.
+    const ZLIB_LEVEL: &[u8] = &[
+        0x78, 0x01, // header
+        0x01, 4, 0, 0xFB, 0xFF, // final stored block of 4 bytes
+        1, 2, 3, 4, // data
+        0x00, 0x18, 0x00, 0x0B, // adler32
+    ];
+
+    /// Replace the level of `file()` with a supercompressed one.
+    fn supercompressed(scheme: u32, level: &[u8], len_uncompressed: u64) -> Vec<u8> {
+        let mut data = file();
+        data.truncate(LEVEL_OFFSET);
+        data.extend_from_slice(level);
+        set_u32(&mut data, 44, scheme);
+        set_u64(&mut data, 88, level.len() as u64);
+        set_u64(&mut data, 96, len_uncompressed);
+        data
+    }
+
+    fn error(data: &[u8]) -> KtxError {
+        match Image::new(data) {
+            Ok(_) => panic!("malformed file parsed successfully"),
+            Err(err) => err.downcast::<KtxError>().unwrap(),
+        }
+    }
+
+    #[test]
+    fn valid() {
+        let image = Image::new(&file()).unwrap();
+        assert_eq!(image.levels, [[1, 2, 3, 4]]);
+        assert_eq!(image.dfd.color_model, COLOR_MODEL_RGBSDA);
+        assert!(image.dfd.is_srgb());
+        assert_eq!(image.dfd.samples.len(), 1);
+        assert_eq!(image.dfd.samples[0].bit_offset, 24);
+        assert_eq!(image.dfd.samples[0].bit_length, 8);
+        assert_eq!(image.dfd.samples[0].channel_type, CHANNEL_RGBSDA_ALPHA);
+    }
+
+    #[test]
+    fn bad_magic() {
+        let mut data = file();
+        data[1] = b'X';
+        assert!(matches!(error(&data), KtxError::BadMagic));
+        assert!(matches!(error(b"PNG"), KtxError::BadMagic));
+    }
+
+    #[test]
+    fn truncated_header() {
+        let data = file();
+        assert!(matches!(error(&data[..79]), KtxError::TruncatedHeader));
+        assert!(matches!(error(&data[..4]), KtxError::TruncatedHeader));
+    }
+
+    #[test]
+    fn level_index_past_eof() {
+        let mut data = file();
+        set_u32(&mut data, 40, 1000);
+        assert!(matches!(error(&data), KtxError::TruncatedHeader));
+    }
+
+    #[test]
+    fn level_out_of_bounds() {
+        let mut data = file();
+        set_u64(&mut data, 88, 5);
+        assert!(matches!(
+            error(&data),
+            KtxError::LevelOutOfBounds { level: 0 }
+        ));
+        set_u64(&mut data, 80, u64::MAX);
+        assert!(matches!(
+            error(&data),
+            KtxError::LevelOutOfBounds { level: 0 }
+        ));
+    }
+
+    #[test]
+    fn level_size_mismatch() {
+        let mut data = file();
+        set_u64(&mut data, 96, 5);
+        assert!(matches!(
+            error(&data),
+            KtxError::LevelSizeMismatch {
+                level: 0,
+                expected: 5,
+                actual: 4
+            }
+        ));
+    }
+
+    #[test]
+    fn supercompression() {
+        for &(scheme, level) in &[
+            (SUPERCOMPRESSION_ZSTD, ZSTD_LEVEL),
+            (SUPERCOMPRESSION_ZLIB, ZLIB_LEVEL),
+        ] {
+            let image = Image::new(&supercompressed(scheme, level, 4)).unwrap();
+            assert_eq!(image.levels, [[1, 2, 3, 4]], "scheme {}", scheme);
+
+            // Decoded level shorter than `uncompressedByteLength`.
+            let data = supercompressed(scheme, level, 5);
+            assert!(
+                matches!(
+                    error(&data),
+                    KtxError::LevelSizeMismatch {
+                        level: 0,
+                        expected: 5,
+                        actual: 4
+                    }
+                ),
+                "scheme {}",
+                scheme
+            );
+
+            // Decoding stops one byte past `uncompressedByteLength`.
+            let data = supercompressed(scheme, level, 2);
+            assert!(
+                matches!(
+                    error(&data),
+                    KtxError::LevelSizeMismatch {
+                        level: 0,
+                        expected: 2,
+                        actual: 3
+                    }
+                ),
+                "scheme {}",
+                scheme
+            );
+        }
+    }
+
+    #[test]
+    fn unknown_scheme() {
+        let mut data = file();
+        set_u32(&mut data, 44, 7);
+        assert!(matches!(error(&data), KtxError::UnsupportedScheme(7)));
+    }
+
+    #[test]
+    fn dfd_out_of_bounds() {
+        let mut data = file();
+        set_u32(&mut data, 52, 1000);
+        assert!(matches!(error(&data), KtxError::BadIndex(_)));
+    }
+
+    #[test]
+    fn truncated_dfd() {
+        let mut data = file();
+        set_u32(&mut data, 52, 20);
+        assert!(matches!(error(&data), KtxError::BadDataFormatDescriptor(_)));
+    }
+
+    #[test]
+    fn sgd_out_of_bounds() {
+        let mut data = file();
+        set_u64(&mut data, 64, u64::MAX);
+        set_u64(&mut data, 72, 2);
+        assert!(matches!(error(&data), KtxError::BadIndex(_)));
     }
 }
@@ -0,0 +1,89 @@
+use crate::gltf::{self, LightType};
+use crate::math;
+use crate::scene::LightInstance;
+use glace::{f32x4, vec4};
+
+pub const MAX_LIGHTS: usize = 16;
+
+/// Punctual light in world space as consumed by the fragment shader.
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct Light {
+    /// Position `xyz`, light type in `w` (0: directional, 1: point, 2: spot).
+    position: f32x4,
+    /// Direction the light is pointing at in `xyz`, inverse squared range in `w` (0 if unlimited).
+    direction: f32x4,
+    /// Color premultiplied by intensity.
+    color: f32x4,
+    /// Angular attenuation `scale` and `offset` applied to `cos(angle)`.
+    spot: f32x4,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct LocalsLights {
+    num_lights: u32,
+    _pad: [u32; 3],
+    lights: [Light; MAX_LIGHTS],
+}
+
+impl Light {
+    fn new(light: &gltf::Light, light_to_world: &math::Mat4) -> Self {
+        let [px, py, pz] = math::transform_point(light_to_world, [0.0; 3]);
+        // Lights are pointing along their local -Z axis.
+        let [dx, dy, dz] =
+            math::normalize(math::transform_vector(light_to_world, [0.0, 0.0, -1.0]));
+        let [r, g, b] = light.color;
+        let intensity = light.intensity;
+
+        let kind = match light.ty {
+            LightType::Directional => 0.0,
+            LightType::Point => 1.0,
+            LightType::Spot => 2.0,
+        };
+        let inv_range_sq = match (light.ty, light.range) {
+            (LightType::Directional, _) | (_, None) => 0.0,
+            (_, Some(range)) => (range * range).recip(),
+        };
+        let (scale, offset) = match (light.ty, &light.spot) {
+            (LightType::Spot, Some(spot)) => {
+                let cos_outer = spot.outer_cone_angle.cos();
+                let cos_inner = spot.inner_cone_angle.cos();
+                let scale = (cos_inner - cos_outer).max(0.001).recip();
+                (scale, -cos_outer * scale)
+            }
+            _ => (0.0, 1.0),
+        };
+
+        Light {
+            position: vec4(px, py, pz, kind),
+            direction: vec4(dx, dy, dz, inv_range_sq),
+            color: vec4(r * intensity, g * intensity, b * intensity, 0.0),
+            spot: vec4(scale, offset, 0.0, 0.0),
+        }
+    }
+}
+
+impl LocalsLights {
+    /// Gather the lights placed in the scene, lights exceeding `MAX_LIGHTS` are dropped.
+    pub fn new(lights: &[gltf::Light], instances: &[LightInstance]) -> Self {
+        let empty = Light {
+            position: vec4(0.0, 0.0, 0.0, 0.0),
+            direction: vec4(0.0, 0.0, 0.0, 0.0),
+            color: vec4(0.0, 0.0, 0.0, 0.0),
+            spot: vec4(0.0, 0.0, 0.0, 0.0),
+        };
+        let mut locals = LocalsLights {
+            num_lights: 0,
+            _pad: [0; 3],
+            lights: [empty; MAX_LIGHTS],
+        };
+
+        for (dst, instance) in locals.lights.iter_mut().zip(instances) {
+            *dst = Light::new(&lights[instance.light], &instance.light_to_world);
+            locals.num_lights += 1;
+        }
+
+        locals
+    }
+}
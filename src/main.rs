@@ -1,6 +1,6 @@
+use std::collections::hash_map::{Entry, HashMap};
 use std::fs;
-use std::mem;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::time::Instant;
 
 use self::camera::{Camera, InputMap};
@@ -8,13 +8,26 @@ use glace::{f32x4, f32x4x4, vec3, vec4};
 use raw_gl_context::{GlConfig, GlContext, Profile};
 use winit::{
     dpi::LogicalSize,
-    event::{DeviceEvent, Event, WindowEvent},
+    event::{DeviceEvent, ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent},
     event_loop::{ControlFlow, EventLoop},
     window::WindowBuilder,
 };
 
+mod accessor;
+mod animation;
+mod basisu;
 mod camera;
+mod glb;
+mod gltf;
 mod ktx;
+mod light;
+mod material;
+mod math;
+mod mesh;
+mod scene;
+mod skin;
+mod tangent;
+mod uri;
 
 #[repr(C)]
 #[derive(Debug)]
@@ -25,6 +38,23 @@ struct LocalsPbr {
     specular_mipmaps: u32,
 }
 
+#[repr(C)]
+#[derive(Debug)]
+struct LocalsMesh {
+    model_to_world: f32x4x4,
+    normal_to_world: f32x4x4,
+    skinned: u32,
+    morph_targets: u32,
+    _pad: [u32; 2],
+    morph_weights: [[f32; 4]; mesh::MAX_MORPH_TARGETS / 4],
+}
+
+#[repr(C)]
+#[derive(Debug)]
+struct LocalsSkin {
+    joints: [f32x4x4; skin::MAX_JOINTS],
+}
+
 #[repr(C)]
 #[derive(Debug)]
 struct LocalsSkybox {
@@ -32,10 +62,55 @@ struct LocalsSkybox {
     clip_to_view: f32x4x4,
 }
 
+/// Convert a column-major glTF matrix into the row layout used by the shaders.
+fn mat4(m: &math::Mat4) -> f32x4x4 {
+    f32x4x4 {
+        x: vec4(m[0][0], m[1][0], m[2][0], m[3][0]),
+        y: vec4(m[0][1], m[1][1], m[2][1], m[3][1]),
+        z: vec4(m[0][2], m[1][2], m[2][2], m[3][2]),
+        w: vec4(m[0][3], m[1][3], m[2][3], m[3][3]),
+    }
+}
+
 fn max_mip_levels_2d(width: u32, height: u32) -> u32 {
     (width.max(height) as f32).log2() as u32 + 1
 }
 
+/// Image format and host layout of an uncompressed RGBA KTX2 image.
+fn ktx_format(image: &ktx::Image) -> anyhow::Result<(grr::Format, grr::FormatLayout)> {
+    let dfd = &image.dfd;
+    let channels = dfd
+        .samples
+        .iter()
+        .map(|sample| sample.channel_type)
+        .collect::<Vec<_>>();
+    anyhow::ensure!(
+        dfd.color_model == ktx::COLOR_MODEL_RGBSDA
+            && channels == [0, 1, 2, ktx::CHANNEL_RGBSDA_ALPHA],
+        "unsupported KTX2 format, expected RGBA"
+    );
+
+    let sample = dfd.samples[0];
+    anyhow::ensure!(
+        dfd.samples
+            .iter()
+            .all(|s| s.bit_length == sample.bit_length && s.qualifiers == sample.qualifiers),
+        "unsupported KTX2 format, mixed channel types"
+    );
+    let float = sample.qualifiers & ktx::QUALIFIER_FLOAT != 0;
+    Ok(match (sample.bit_length, float, dfd.is_srgb()) {
+        (8, false, true) => (grr::Format::R8G8B8A8_SRGB, grr::FormatLayout::U8),
+        (8, false, false) => (grr::Format::R8G8B8A8_UNORM, grr::FormatLayout::U8),
+        (16, true, _) => (grr::Format::R16G16B16A16_SFLOAT, grr::FormatLayout::F16),
+        (32, true, _) => (grr::Format::R32G32B32A32_SFLOAT, grr::FormatLayout::F32),
+        (bits, float, _) => anyhow::bail!(
+            "unsupported KTX2 format: {} bit {}",
+            bits,
+            if float { "float" } else { "integer" }
+        ),
+    })
+}
+
 fn main() -> anyhow::Result<()> {
     unsafe {
         let event_loop = EventLoop::new();
@@ -77,85 +152,109 @@ fn main() -> anyhow::Result<()> {
         );
 
         let directory = Path::new("assets");
+        let model_path = std::env::args()
+            .nth(1)
+            .map(PathBuf::from)
+            .unwrap_or_else(|| directory.join("SciFiHelmet.gltf"));
+
+        let upload_image = |width: u32,
+                            height: u32,
+                            data: &[u8],
+                            format: grr::Format,
+                            downsample: bool|
+         -> anyhow::Result<grr::Image> {
+            let texture = grr.create_image(
+                grr::ImageType::D2 {
+                    width,
+                    height,
+                    layers: 1,
+                    samples: 1,
+                },
+                format,
+                if downsample {
+                    max_mip_levels_2d(width, height)
+                } else {
+                    1
+                },
+            )?;
+            grr.copy_host_to_image(
+                data,
+                texture,
+                grr::HostImageCopy {
+                    host_layout: grr::MemoryLayout {
+                        base_format: grr::BaseFormat::RGBA,
+                        format_layout: grr::FormatLayout::U8,
+                        row_length: width,
+                        image_height: height,
+                        alignment: 4,
+                    },
+                    image_subresource: grr::SubresourceLayers {
+                        level: 0,
+                        layers: 0..1,
+                    },
+                    image_offset: grr::Offset { x: 0, y: 0, z: 0 },
+                    image_extent: grr::Extent {
+                        width,
+                        height,
+                        depth: 1,
+                    },
+                },
+            );
+            if downsample {
+                grr.generate_mipmaps(texture);
+            }
+
+            Ok(texture)
+        };
 
         let load_png =
-            |name: &str, format: grr::Format, downsample: bool| -> anyhow::Result<grr::Image> {
+            |data: &[u8], format: grr::Format, downsample: bool| -> anyhow::Result<grr::Image> {
                 let start = Instant::now();
-                let path = directory.join(name);
-                let img = image::open(&Path::new(&path)).unwrap().to_rgba8();
+                let img = image::load_from_memory(data)?.to_rgba8();
                 let img_width = img.width();
                 let img_height = img.height();
                 let img_data = img.into_raw();
                 println!("{:?}", start.elapsed());
 
-                let texture = grr.create_image(
-                    grr::ImageType::D2 {
-                        width: img_width,
-                        height: img_height,
-                        layers: 1,
-                        samples: 1,
-                    },
-                    format,
-                    if downsample {
-                        max_mip_levels_2d(img_width, img_height)
-                    } else {
-                        1
-                    },
-                )?;
-                grr.copy_host_to_image(
-                    &img_data,
-                    texture,
-                    grr::HostImageCopy {
-                        host_layout: grr::MemoryLayout {
-                            base_format: grr::BaseFormat::RGBA,
-                            format_layout: grr::FormatLayout::U8,
-                            row_length: img_width,
-                            image_height: img_height,
-                            alignment: 4,
-                        },
-                        image_subresource: grr::SubresourceLayers {
-                            level: 0,
-                            layers: 0..1,
-                        },
-                        image_offset: grr::Offset { x: 0, y: 0, z: 0 },
-                        image_extent: grr::Extent {
-                            width: img_width,
-                            height: img_height,
-                            depth: 1,
-                        },
-                    },
-                );
-                if downsample {
-                    grr.generate_mipmaps(texture);
-                }
+                upload_image(img_width, img_height, &img_data, format, downsample)
+            };
 
-                Ok(texture)
+        // Color space and alpha mode of KTX2 textures are taken from the data format descriptor.
+        // Premultiplied images are uploaded as is, returning whether they are premultiplied.
+        let load_ktx2 = |data: &[u8], downsample: bool| -> anyhow::Result<(grr::Image, bool)> {
+            let image = ktx::Image::new(data)?;
+            let img_data = basisu::decode_rgba8(&image, 0)?;
+            let format = if image.dfd.is_srgb() {
+                grr::Format::R8G8B8A8_SRGB
+            } else {
+                grr::Format::R8G8B8A8_UNORM
             };
+            let texture = upload_image(
+                image.header.pixel_width,
+                image.header.pixel_height,
+                &img_data,
+                format,
+                downsample,
+            )?;
+            Ok((texture, image.dfd.is_premultiplied()))
+        };
 
-        let num_indices = 70_074;
-        let bin = std::fs::read(directory.join("SciFiHelmet.bin"))?;
-        let index_buffer =
-            grr.create_buffer_from_host(&bin[..num_indices * 4], grr::MemoryFlags::DEVICE_LOCAL)?;
-        let vertex_buffer =
-            grr.create_buffer_from_host(&bin[num_indices * 4..], grr::MemoryFlags::DEVICE_LOCAL)?;
-
-        let albedo = load_png(
-            "SciFiHelmet_BaseColor.png",
-            grr::Format::R8G8B8A8_SRGB,
-            true,
-        )?;
-        let normals = load_png("SciFiHelmet_Normal.png", grr::Format::R8G8B8_UNORM, true)?;
-        let metal_roughness = load_png(
-            "SciFiHelmet_MetallicRoughness.png",
-            grr::Format::R8G8B8_UNORM,
-            true,
-        )?;
-        let ambient_occlusion = load_png(
-            "SciFiHelmet_AmbientOcclusion.png",
-            grr::Format::R8G8B8_UNORM,
-            true,
-        )?;
-        let lut_ggx = load_png("lut_ggx.png", grr::Format::R8G8B8_UNORM, false)?;
+        let model = gltf::Gltf::open(&model_path)?;
+        let meshes = mesh::load_meshes(&grr, &model)?;
+
+        let skins = skin::load_skins(&model)?;
+        let lights = model
+            .document
+            .extensions
+            .lights_punctual
+            .as_ref()
+            .map_or_else(Vec::new, |ext| ext.lights.clone());
+
+        let mut scene = scene::Scene::new(&model.document);
+        let animations = animation::load_animations(&model)?;
+        // Only the first animation is played.
+        let mut clock =
+            animation::Clock::new(animations.first().map_or(0.0, |anim| anim.duration()));
 
         let sampler = grr.create_sampler(grr::SamplerDesc {
             min_filter: grr::Filter::Linear,
@@ -172,32 +271,77 @@ fn main() -> anyhow::Result<()> {
             border_color: [0.0, 0.0, 0.0, 0.0],
         })?;
 
-        let vertex_array = grr.create_vertex_array(&[
-            grr::VertexAttributeDesc {
-                location: 0,
-                binding: 0,
-                format: grr::VertexFormat::Xyz32Float,
-                offset: 0,
-            },
-            grr::VertexAttributeDesc {
-                location: 1,
-                binding: 1,
-                format: grr::VertexFormat::Xyz32Float,
-                offset: 0,
-            },
-            grr::VertexAttributeDesc {
-                location: 2,
-                binding: 2,
-                format: grr::VertexFormat::Xy32Float,
-                offset: 0,
-            },
-            grr::VertexAttributeDesc {
-                location: 3,
-                binding: 3,
-                format: grr::VertexFormat::Xyzw32Float,
-                offset: 0,
-            },
-        ])?;
+        // The default sampler for textures without sampler is stored last.
+        let mut samplers = model
+            .document
+            .samplers
+            .iter()
+            .map(|sampler| grr.create_sampler(material::sampler_desc(sampler)))
+            .collect::<Result<Vec<_>, _>>()?;
+        samplers.push(grr.create_sampler(material::sampler_desc(&gltf::Sampler::default()))?);
+        let default_sampler = samplers.len() - 1;
+
+        let fallback = material::Fallback {
+            white: upload_image(1, 1, &[255; 4], grr::Format::R8G8B8A8_UNORM, false)?.as_view(),
+            normal: upload_image(
+                1,
+                1,
+                &[128, 128, 255, 255],
+                grr::Format::R8G8B8A8_UNORM,
+                false,
+            )?
+            .as_view(),
+            sampler: samplers[default_sampler],
+        };
+
+        let mut textures = HashMap::new();
+        let mut material_texture =
+            |texture: usize, srgb: bool| -> anyhow::Result<(material::Texture, bool)> {
+                let (image, premultiplied) = match textures.entry((texture, srgb)) {
+                    Entry::Occupied(entry) => *entry.get(),
+                    Entry::Vacant(entry) => {
+                        let format = if srgb {
+                            grr::Format::R8G8B8A8_SRGB
+                        } else {
+                            grr::Format::R8G8B8_UNORM
+                        };
+                        let data = model.texture_image_data(texture)?;
+                        let image = if data.starts_with(&ktx::FILE_IDENTIFIER) {
+                            load_ktx2(&data, true)?
+                        } else {
+                            (load_png(&data, format, true)?, false)
+                        };
+                        *entry.insert(image)
+                    }
+                };
+                let sampler = model.document.textures[texture]
+                    .sampler
+                    .unwrap_or(default_sampler);
+                Ok(((image.as_view(), samplers[sampler]), premultiplied))
+            };
+
+        // The default material for primitives without material is stored last.
+        let mut materials = model
+            .document
+            .materials
+            .iter()
+            .map(|material| {
+                material::Material::new(&grr, material, &fallback, &mut material_texture)
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        materials.push(material::Material::new(
+            &grr,
+            &gltf::Material::default(),
+            &fallback,
+            &mut material_texture,
+        )?);
+        let default_material = materials.len() - 1;
+
+        let lut_ggx = load_png(
+            &fs::read(directory.join("lut_ggx.png"))?,
+            grr::Format::R8G8B8_UNORM,
+            false,
+        )?;
 
         let empty_array = grr.create_vertex_array(&[])?;
 
@@ -240,6 +384,67 @@ fn main() -> anyhow::Result<()> {
             stencil_back: grr::StencilFace::KEEP,
         };
 
+        let pbr_blend_state_ds = grr::DepthStencil {
+            depth_write: false,
+            ..pbr_state_ds
+        };
+
+        let rasterization_state = grr::Rasterization {
+            depth_clamp: false,
+            rasterizer_discard: false,
+            polygon_mode: grr::PolygonMode::Fill,
+            cull_mode: None,
+            front_face: grr::FrontFace::CounterClockwise,
+            depth_bias: false,
+        };
+
+        let opaque_state = grr::ColorBlend {
+            attachments: vec![grr::ColorBlendAttachment {
+                blend_enable: false,
+                color: grr::BlendChannel {
+                    src_factor: grr::BlendFactor::One,
+                    dst_factor: grr::BlendFactor::Zero,
+                    blend_op: grr::BlendOp::Add,
+                },
+                alpha: grr::BlendChannel {
+                    src_factor: grr::BlendFactor::One,
+                    dst_factor: grr::BlendFactor::Zero,
+                    blend_op: grr::BlendOp::Add,
+                },
+            }],
+        };
+        let blend_state = grr::ColorBlend {
+            attachments: vec![grr::ColorBlendAttachment {
+                blend_enable: true,
+                color: grr::BlendChannel {
+                    src_factor: grr::BlendFactor::SrcAlpha,
+                    dst_factor: grr::BlendFactor::OneMinusSrcAlpha,
+                    blend_op: grr::BlendOp::Add,
+                },
+                alpha: grr::BlendChannel {
+                    src_factor: grr::BlendFactor::One,
+                    dst_factor: grr::BlendFactor::OneMinusSrcAlpha,
+                    blend_op: grr::BlendOp::Add,
+                },
+            }],
+        };
+        // Blending for materials with premultiplied base color.
+        let premultiplied_blend_state = grr::ColorBlend {
+            attachments: vec![grr::ColorBlendAttachment {
+                blend_enable: true,
+                color: grr::BlendChannel {
+                    src_factor: grr::BlendFactor::One,
+                    dst_factor: grr::BlendFactor::OneMinusSrcAlpha,
+                    blend_op: grr::BlendOp::Add,
+                },
+                alpha: grr::BlendChannel {
+                    src_factor: grr::BlendFactor::One,
+                    dst_factor: grr::BlendFactor::OneMinusSrcAlpha,
+                    blend_op: grr::BlendOp::Add,
+                },
+            }],
+        };
+
         let skybox_vs = grr.create_shader(
             grr::ShaderStage::Vertex,
             grr::ShaderSource::Spirv {
@@ -279,6 +484,7 @@ fn main() -> anyhow::Result<()> {
 
         let specular = fs::read(directory.join("specular.ktx2"))?;
         let specular_raw = ktx::Image::new(&specular)?;
+        let (specular_format, specular_layout) = ktx_format(&specular_raw)?;
         let specular_map = grr.create_image(
             grr::ImageType::D2 {
                 width: specular_raw.header.pixel_width,
@@ -286,13 +492,13 @@ fn main() -> anyhow::Result<()> {
                 layers: 6,
                 samples: 1,
             },
-            grr::Format::R16G16B16A16_SFLOAT,
+            specular_format,
             specular_raw.header.level_count,
         )?;
         let specular_view = grr.create_image_view(
             specular_map,
             grr::ImageViewType::Cube,
-            grr::Format::R16G16B16A16_SFLOAT,
+            specular_format,
             grr::SubresourceRange {
                 levels: 0..specular_raw.header.level_count,
                 layers: 0..6,
@@ -306,7 +512,7 @@ fn main() -> anyhow::Result<()> {
                 grr::HostImageCopy {
                     host_layout: grr::MemoryLayout {
                         base_format: grr::BaseFormat::RGBA,
-                        format_layout: grr::FormatLayout::F16,
+                        format_layout: specular_layout,
                         row_length: specular_raw.header.pixel_width >> i,
                         image_height: specular_raw.header.pixel_height >> i,
                         alignment: 4,
@@ -327,6 +533,7 @@ fn main() -> anyhow::Result<()> {
 
         let diffuse = fs::read(directory.join("diffuse.ktx2"))?;
         let diffuse_raw = ktx::Image::new(&diffuse)?;
+        let (diffuse_format, diffuse_layout) = ktx_format(&diffuse_raw)?;
         let diffuse_map = grr.create_image(
             grr::ImageType::D2 {
                 width: diffuse_raw.header.pixel_width,
@@ -334,13 +541,13 @@ fn main() -> anyhow::Result<()> {
                 layers: 6,
                 samples: 1,
             },
-            grr::Format::R16G16B16A16_SFLOAT,
+            diffuse_format,
             diffuse_raw.header.level_count,
         )?;
         let diffuse_view = grr.create_image_view(
             diffuse_map,
             grr::ImageViewType::Cube,
-            grr::Format::R16G16B16A16_SFLOAT,
+            diffuse_format,
             grr::SubresourceRange {
                 levels: 0..diffuse_raw.header.level_count,
                 layers: 0..6,
@@ -354,7 +561,7 @@ fn main() -> anyhow::Result<()> {
                 grr::HostImageCopy {
                     host_layout: grr::MemoryLayout {
                         base_format: grr::BaseFormat::RGBA,
-                        format_layout: grr::FormatLayout::F16,
+                        format_layout: diffuse_layout,
                         row_length: diffuse_raw.header.pixel_width >> i,
                         image_height: diffuse_raw.header.pixel_height >> i,
                         alignment: 4,
@@ -377,12 +584,32 @@ fn main() -> anyhow::Result<()> {
         let mut input = InputMap::new();
 
         event_loop.run(move |event, _, control_flow| {
-            *control_flow = ControlFlow::Wait;
+            *control_flow = if clock.playing && !animations.is_empty() {
+                ControlFlow::Poll
+            } else {
+                ControlFlow::Wait
+            };
 
             match event {
                 Event::LoopDestroyed => return,
                 Event::WindowEvent { event, .. } => match event {
                     WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                state: ElementState::Pressed,
+                                virtual_keycode: Some(key),
+                                ..
+                            },
+                        ..
+                    } => match key {
+                        VirtualKeyCode::Space if clock.playing => clock.pause(),
+                        VirtualKeyCode::Space => clock.play(),
+                        VirtualKeyCode::L => clock.looping = !clock.looping,
+                        VirtualKeyCode::Up => clock.speed *= 2.0,
+                        VirtualKeyCode::Down => clock.speed *= 0.5,
+                        _ => (),
+                    },
                     _ => (),
                 },
                 Event::DeviceEvent { event, .. } => match event {
@@ -471,6 +698,8 @@ fn main() -> anyhow::Result<()> {
                     grr.bind_pipeline(skybox_pipeline);
                     grr.bind_vertex_array(empty_array);
                     grr.bind_depth_stencil_state(&skybox_state_ds);
+                    grr.bind_color_blend_state(&opaque_state);
+                    grr.bind_rasterization_state(&rasterization_state);
                     grr.bind_uniform_buffers(
                         0,
                         &[grr::BufferRange {
@@ -483,44 +712,8 @@ fn main() -> anyhow::Result<()> {
                     grr.bind_samplers(0, &[sampler]);
                     grr.draw(grr::Primitive::Triangles, 0..3, 0..1);
 
-                    grr.bind_vertex_array(vertex_array);
                     grr.bind_pipeline(pbr_pipeline);
                     grr.bind_depth_stencil_state(&pbr_state_ds);
-                    grr.bind_vertex_buffers(
-                        vertex_array,
-                        0,
-                        &[
-                            // position
-                            grr::VertexBufferView {
-                                buffer: vertex_buffer,
-                                offset: 0,
-                                stride: (3 * mem::size_of::<f32>()) as _,
-                                input_rate: grr::InputRate::Vertex,
-                            },
-                            // normal
-                            grr::VertexBufferView {
-                                buffer: vertex_buffer,
-                                offset: 840_888,
-                                stride: (3 * mem::size_of::<f32>()) as _,
-                                input_rate: grr::InputRate::Vertex,
-                            },
-                            // texcoord
-                            grr::VertexBufferView {
-                                buffer: vertex_buffer,
-                                offset: 2_802_960,
-                                stride: (2 * mem::size_of::<f32>()) as _,
-                                input_rate: grr::InputRate::Vertex,
-                            },
-                            // tangent
-                            grr::VertexBufferView {
-                                buffer: vertex_buffer,
-                                offset: 1_681_776,
-                                stride: (4 * mem::size_of::<f32>()) as _,
-                                input_rate: grr::InputRate::Vertex,
-                            },
-                        ],
-                    );
-                    grr.bind_index_buffer(vertex_array, index_buffer);
                     grr.bind_uniform_buffers(
                         0,
                         &[grr::BufferRange {
@@ -529,28 +722,231 @@ fn main() -> anyhow::Result<()> {
                             size: std::mem::size_of::<LocalsPbr>() as _,
                         }],
                     );
-                    grr.bind_image_views(
-                        0,
-                        &[
-                            albedo.as_view(),
-                            normals.as_view(),
-                            metal_roughness.as_view(),
-                            ambient_occlusion.as_view(),
-                            diffuse_view,
-                            specular_view,
-                            lut_ggx.as_view(),
-                        ],
-                    );
-                    grr.bind_samplers(0, &[sampler]);
 
-                    grr.draw_indexed(
-                        grr::Primitive::Triangles,
-                        grr::IndexTy::U32,
-                        0..num_indices as _,
-                        0..1,
-                        0,
+                    let time = clock.tick();
+                    if let Some(animation) = animations.first() {
+                        animation.apply(time, &mut scene);
+                    }
+                    let world_transforms = scene.world_transforms();
+                    let instances = scene.instances();
+
+                    let locals_lights = light::LocalsLights::new(&lights, &scene.lights());
+                    let u_locals_lights = grr
+                        .create_buffer_from_host(
+                            grr::as_u8_slice(&[locals_lights]),
+                            grr::MemoryFlags::DEVICE_LOCAL,
+                        )
+                        .unwrap();
+                    grr.bind_uniform_buffers(
+                        4,
+                        &[grr::BufferRange {
+                            buffer: u_locals_lights,
+                            offset: 0,
+                            size: std::mem::size_of::<light::LocalsLights>() as _,
+                        }],
                     );
 
+                    let instance_locals = instances
+                        .iter()
+                        .map(|instance| {
+                            // Node weights override the defaults of the mesh.
+                            let weights = match scene.nodes[instance.node].weights {
+                                ref weights if !weights.is_empty() => weights,
+                                _ => &meshes[instance.mesh].weights,
+                            };
+                            let mut morph_weights = [[0.0; 4]; mesh::MAX_MORPH_TARGETS / 4];
+                            for (i, &weight) in
+                                weights.iter().take(mesh::MAX_MORPH_TARGETS).enumerate()
+                            {
+                                morph_weights[i / 4][i % 4] = weight;
+                            }
+                            let locals_mesh = LocalsMesh {
+                                model_to_world: mat4(&instance.model_to_world),
+                                normal_to_world: mat4(&math::normal_matrix(
+                                    &instance.model_to_world,
+                                )),
+                                skinned: instance.skin.is_some() as u32,
+                                morph_targets: meshes[instance.mesh].num_targets as u32,
+                                _pad: [0; 2],
+                                morph_weights,
+                            };
+                            let u_locals_mesh = grr
+                                .create_buffer_from_host(
+                                    grr::as_u8_slice(&[locals_mesh]),
+                                    grr::MemoryFlags::DEVICE_LOCAL,
+                                )
+                                .unwrap();
+                            let u_locals_skin = instance.skin.map(|index| {
+                                let mut locals_skin = LocalsSkin {
+                                    joints: [mat4(&math::IDENTITY); skin::MAX_JOINTS],
+                                };
+                                for (joint, m) in locals_skin
+                                    .joints
+                                    .iter_mut()
+                                    .zip(skins[index].joint_matrices(&world_transforms))
+                                {
+                                    *joint = mat4(&m);
+                                }
+                                grr.create_buffer_from_host(
+                                    grr::as_u8_slice(&[locals_skin]),
+                                    grr::MemoryFlags::DEVICE_LOCAL,
+                                )
+                                .unwrap()
+                            });
+                            // Mirroring transforms invert the winding order of the triangles.
+                            let front_face = if math::determinant3(&instance.model_to_world) < 0.0 {
+                                grr::FrontFace::Clockwise
+                            } else {
+                                grr::FrontFace::CounterClockwise
+                            };
+                            (u_locals_mesh, u_locals_skin, front_face)
+                        })
+                        .collect::<Vec<_>>();
+
+                    let draw_primitive =
+                        |(u_locals_mesh, u_locals_skin, front_face): (
+                            grr::Buffer,
+                            Option<grr::Buffer>,
+                            grr::FrontFace,
+                        ),
+                         primitive: &mesh::Primitive,
+                         material: &material::Material| {
+                            let textures = material.textures();
+
+                            grr.bind_rasterization_state(&grr::Rasterization {
+                                cull_mode: if material.double_sided {
+                                    None
+                                } else {
+                                    Some(grr::CullMode::Back)
+                                },
+                                front_face,
+                                ..rasterization_state
+                            });
+
+                            grr.bind_vertex_array(primitive.vertex_array);
+                            grr.bind_vertex_buffers(
+                                primitive.vertex_array,
+                                0,
+                                &primitive.vertex_buffers,
+                            );
+                            grr.bind_uniform_buffers(
+                                1,
+                                &[
+                                    grr::BufferRange {
+                                        buffer: u_locals_mesh,
+                                        offset: 0,
+                                        size: std::mem::size_of::<LocalsMesh>() as _,
+                                    },
+                                    grr::BufferRange {
+                                        buffer: material.u_locals,
+                                        offset: 0,
+                                        size: std::mem::size_of::<material::LocalsMaterial>() as _,
+                                    },
+                                ],
+                            );
+                            if let Some(u_locals_skin) = u_locals_skin {
+                                grr.bind_uniform_buffers(
+                                    3,
+                                    &[grr::BufferRange {
+                                        buffer: u_locals_skin,
+                                        offset: 0,
+                                        size: std::mem::size_of::<LocalsSkin>() as _,
+                                    }],
+                                );
+                            }
+                            grr.bind_image_views(
+                                0,
+                                &[
+                                    textures[0].0,
+                                    textures[1].0,
+                                    textures[2].0,
+                                    textures[3].0,
+                                    diffuse_view,
+                                    specular_view,
+                                    lut_ggx.as_view(),
+                                    textures[4].0,
+                                    primitive.morph_targets.unwrap_or(fallback.white),
+                                ],
+                            );
+                            grr.bind_samplers(
+                                0,
+                                &[
+                                    textures[0].1,
+                                    textures[1].1,
+                                    textures[2].1,
+                                    textures[3].1,
+                                    sampler,
+                                    sampler,
+                                    sampler,
+                                    textures[4].1,
+                                    sampler,
+                                ],
+                            );
+
+                            match primitive.indices {
+                                Some(ref indices) => {
+                                    grr.bind_index_buffer(primitive.vertex_array, indices.buffer);
+                                    grr.draw_indexed(
+                                        primitive.topology,
+                                        indices.ty,
+                                        indices.first..indices.first + indices.count,
+                                        0..1,
+                                        0,
+                                    );
+                                }
+                                None => {
+                                    grr.draw(primitive.topology, 0..primitive.num_vertices, 0..1);
+                                }
+                            }
+                        };
+
+                    // Opaque and alpha tested primitives, blended ones are deferred.
+                    let mut blend_draws = Vec::new();
+                    for (instance, locals) in instances.iter().zip(&instance_locals) {
+                        for primitive in &meshes[instance.mesh].primitives {
+                            let material =
+                                &materials[primitive.material.unwrap_or(default_material)];
+                            if material.alpha_mode == gltf::AlphaMode::Blend {
+                                let [x, y, z] = math::transform_point(
+                                    &instance.model_to_world,
+                                    primitive.center,
+                                );
+                                let distance =
+                                    (x - eye.x).powi(2) + (y - eye.y).powi(2) + (z - eye.z).powi(2);
+                                // Degenerate transforms yield NaN, draw those first.
+                                let distance = if distance.is_nan() {
+                                    f32::INFINITY
+                                } else {
+                                    distance
+                                };
+                                blend_draws.push((distance, *locals, primitive, material));
+                            } else {
+                                draw_primitive(*locals, primitive, material);
+                            }
+                        }
+                    }
+
+                    // Blended primitives back-to-front without depth writes.
+                    // Distances are never NaN, which makes this a total order.
+                    blend_draws.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
+                    grr.bind_depth_stencil_state(&pbr_blend_state_ds);
+                    for (_, locals, primitive, material) in blend_draws {
+                        grr.bind_color_blend_state(if material.premultiplied_alpha {
+                            &premultiplied_blend_state
+                        } else {
+                            &blend_state
+                        });
+                        draw_primitive(locals, primitive, material);
+                    }
+
+                    for (u_locals_mesh, u_locals_skin, _) in instance_locals {
+                        grr.delete_buffer(u_locals_mesh);
+                        if let Some(u_locals_skin) = u_locals_skin {
+                            grr.delete_buffer(u_locals_skin);
+                        }
+                    }
+
+                    grr.delete_buffer(u_locals_lights);
                     grr.delete_buffer(u_locals);
                     grr.delete_buffer(u_locals_inv);
 
@@ -0,0 +1,270 @@
+use crate::gltf;
+use glace::{f32x4, vec4};
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct LocalsMaterial {
+    base_color_factor: f32x4,
+    emissive_factor: f32x4,
+    base_color_transform: UvTransform,
+    normal_transform: UvTransform,
+    metallic_roughness_transform: UvTransform,
+    occlusion_transform: UvTransform,
+    emissive_transform: UvTransform,
+    metallic_factor: f32,
+    roughness_factor: f32,
+    normal_scale: f32,
+    occlusion_strength: f32,
+    alpha_cutoff: f32,
+    alpha_mode: u32,
+    base_color_tex_coord: u32,
+    normal_tex_coord: u32,
+    metallic_roughness_tex_coord: u32,
+    occlusion_tex_coord: u32,
+    emissive_tex_coord: u32,
+}
+
+/// First two rows of the 3x3 texture coordinate transformation.
+///
+/// The last row is always `(0, 0, 1)`.
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+struct UvTransform {
+    x: f32x4,
+    y: f32x4,
+}
+
+impl UvTransform {
+    /// Compose `T * R * S` from `KHR_texture_transform`, identity if absent.
+    fn new(transform: Option<&gltf::TextureTransform>) -> Self {
+        let default = gltf::TextureTransform::default();
+        let transform = transform.unwrap_or(&default);
+        let [ox, oy] = transform.offset;
+        let [sx, sy] = transform.scale;
+        let (sin, cos) = transform.rotation.sin_cos();
+
+        UvTransform {
+            x: vec4(cos * sx, sin * sy, ox, 0.0),
+            y: vec4(-sin * sx, cos * sy, oy, 0.0),
+        }
+    }
+}
+
+/// 1x1 textures bound for material slots without texture.
+pub struct Fallback {
+    pub white: grr::ImageView,
+    pub normal: grr::ImageView,
+    pub sampler: grr::Sampler,
+}
+
+/// Image and sampler bound to a material texture slot.
+pub type Texture = (grr::ImageView, grr::Sampler);
+
+pub struct Material {
+    pub base_color: Texture,
+    pub normal: Texture,
+    pub metallic_roughness: Texture,
+    pub occlusion: Texture,
+    pub emissive: Texture,
+    pub alpha_mode: gltf::AlphaMode,
+    /// Base color is stored with premultiplied alpha and blended accordingly.
+    pub premultiplied_alpha: bool,
+    pub double_sided: bool,
+    pub u_locals: grr::Buffer,
+}
+
+/// Translate a glTF sampler, defaulting to trilinear filtering if unspecified.
+pub fn sampler_desc(sampler: &gltf::Sampler) -> grr::SamplerDesc {
+    let filter = |filter| match filter {
+        gltf::Filter::Nearest
+        | gltf::Filter::NearestMipmapNearest
+        | gltf::Filter::NearestMipmapLinear => grr::Filter::Nearest,
+        gltf::Filter::Linear
+        | gltf::Filter::LinearMipmapNearest
+        | gltf::Filter::LinearMipmapLinear => grr::Filter::Linear,
+    };
+    let address = |wrap| match wrap {
+        gltf::Wrap::ClampToEdge => grr::SamplerAddress::ClampEdge,
+        gltf::Wrap::MirroredRepeat => grr::SamplerAddress::MirrorRepeat,
+        gltf::Wrap::Repeat => grr::SamplerAddress::Repeat,
+    };
+
+    let mip_map = match sampler.min_filter {
+        Some(gltf::Filter::Nearest) | Some(gltf::Filter::Linear) => None,
+        Some(gltf::Filter::NearestMipmapNearest) | Some(gltf::Filter::LinearMipmapNearest) => {
+            Some(grr::Filter::Nearest)
+        }
+        Some(gltf::Filter::NearestMipmapLinear) | Some(gltf::Filter::LinearMipmapLinear) | None => {
+            Some(grr::Filter::Linear)
+        }
+    };
+
+    grr::SamplerDesc {
+        min_filter: sampler.min_filter.map_or(grr::Filter::Linear, filter),
+        mag_filter: sampler.mag_filter.map_or(grr::Filter::Linear, filter),
+        mip_map,
+        address: (
+            address(sampler.wrap_s),
+            address(sampler.wrap_t),
+            grr::SamplerAddress::Repeat,
+        ),
+        lod_bias: 0.0,
+        lod: 0.0..1000.0,
+        compare: None,
+        border_color: [0.0, 0.0, 0.0, 0.0],
+    }
+}
+
+impl Material {
+    /// Upload the material factors and resolve the textures.
+    ///
+    /// `texture` loads the glTF texture with the given index, either as sRGB or linear image,
+    /// together with its sampler and whether its color channels are premultiplied by alpha.
+    pub unsafe fn new<F>(
+        grr: &grr::Device,
+        material: &gltf::Material,
+        fallback: &Fallback,
+        mut texture: F,
+    ) -> anyhow::Result<Self>
+    where
+        F: FnMut(usize, bool) -> anyhow::Result<(Texture, bool)>,
+    {
+        let pbr = &material.pbr_metallic_roughness;
+
+        let mut slot = |info: Option<(usize, usize, &gltf::TextureInfoExtensions)>,
+                        srgb: bool,
+                        default: grr::ImageView| match info {
+            Some((index, tex_coord, extensions)) => {
+                let transform = extensions.texture_transform.as_ref();
+                let tex_coord = transform.and_then(|t| t.tex_coord).unwrap_or(tex_coord);
+                anyhow::ensure!(tex_coord < 2, "unsupported texCoord {}", tex_coord);
+                let (texture, premultiplied) = texture(index, srgb)?;
+                Ok((
+                    texture,
+                    tex_coord as u32,
+                    UvTransform::new(transform),
+                    premultiplied,
+                ))
+            }
+            None => Ok((
+                (default, fallback.sampler),
+                0,
+                UvTransform::new(None),
+                false,
+            )),
+        };
+
+        let (base_color, base_color_tex_coord, base_color_transform, premultiplied_alpha) = slot(
+            pbr.base_color_texture
+                .as_ref()
+                .map(|info| (info.index, info.tex_coord, &info.extensions)),
+            true,
+            fallback.white,
+        )?;
+        let (normal, normal_tex_coord, normal_transform, _) = slot(
+            material
+                .normal_texture
+                .as_ref()
+                .map(|info| (info.index, info.tex_coord, &info.extensions)),
+            false,
+            fallback.normal,
+        )?;
+        let (metallic_roughness, metallic_roughness_tex_coord, metallic_roughness_transform, _) =
+            slot(
+                pbr.metallic_roughness_texture
+                    .as_ref()
+                    .map(|info| (info.index, info.tex_coord, &info.extensions)),
+                false,
+                fallback.white,
+            )?;
+        let (occlusion, occlusion_tex_coord, occlusion_transform, _) = slot(
+            material
+                .occlusion_texture
+                .as_ref()
+                .map(|info| (info.index, info.tex_coord, &info.extensions)),
+            false,
+            fallback.white,
+        )?;
+        let (emissive, emissive_tex_coord, emissive_transform, _) = slot(
+            material
+                .emissive_texture
+                .as_ref()
+                .map(|info| (info.index, info.tex_coord, &info.extensions)),
+            true,
+            fallback.white,
+        )?;
+
+        // Premultiplied base colors need the factor premultiplied as well.
+        let [r, g, b, a] = pbr.base_color_factor;
+        let [r, g, b] = if premultiplied_alpha {
+            [r * a, g * a, b * a]
+        } else {
+            [r, g, b]
+        };
+        let emissive_strength = material
+            .extensions
+            .emissive_strength
+            .as_ref()
+            .map_or(1.0, |ext| ext.emissive_strength);
+        let [er, eg, eb] = material.emissive_factor;
+        let locals = LocalsMaterial {
+            base_color_factor: vec4(r, g, b, a),
+            emissive_factor: vec4(
+                er * emissive_strength,
+                eg * emissive_strength,
+                eb * emissive_strength,
+                0.0,
+            ),
+            base_color_transform,
+            normal_transform,
+            metallic_roughness_transform,
+            occlusion_transform,
+            emissive_transform,
+            metallic_factor: pbr.metallic_factor,
+            roughness_factor: pbr.roughness_factor,
+            normal_scale: material
+                .normal_texture
+                .as_ref()
+                .map_or(1.0, |info| info.scale),
+            occlusion_strength: material
+                .occlusion_texture
+                .as_ref()
+                .map_or(1.0, |info| info.strength),
+            alpha_cutoff: material.alpha_cutoff,
+            alpha_mode: match material.alpha_mode {
+                gltf::AlphaMode::Opaque => 0,
+                gltf::AlphaMode::Mask => 1,
+                gltf::AlphaMode::Blend => 2,
+            },
+            base_color_tex_coord,
+            normal_tex_coord,
+            metallic_roughness_tex_coord,
+            occlusion_tex_coord,
+            emissive_tex_coord,
+        };
+        let u_locals = grr
+            .create_buffer_from_host(grr::as_u8_slice(&[locals]), grr::MemoryFlags::DEVICE_LOCAL)?;
+
+        Ok(Material {
+            base_color,
+            normal,
+            metallic_roughness,
+            occlusion,
+            emissive,
+            alpha_mode: material.alpha_mode,
+            premultiplied_alpha,
+            double_sided: material.double_sided,
+            u_locals,
+        })
+    }
+
+    pub fn textures(&self) -> [Texture; 5] {
+        [
+            self.base_color,
+            self.normal,
+            self.metallic_roughness,
+            self.occlusion,
+            self.emissive,
+        ]
+    }
+}
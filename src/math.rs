@@ -0,0 +1,128 @@
+/// Column-major 4x4 matrix, matching the glTF memory layout.
+pub type Mat4 = [[f32; 4]; 4];
+
+pub const IDENTITY: Mat4 = [
+    [1.0, 0.0, 0.0, 0.0],
+    [0.0, 1.0, 0.0, 0.0],
+    [0.0, 0.0, 1.0, 0.0],
+    [0.0, 0.0, 0.0, 1.0],
+];
+
+pub fn from_cols_array(m: &[f32; 16]) -> Mat4 {
+    [
+        [m[0], m[1], m[2], m[3]],
+        [m[4], m[5], m[6], m[7]],
+        [m[8], m[9], m[10], m[11]],
+        [m[12], m[13], m[14], m[15]],
+    ]
+}
+
+pub fn mul(a: &Mat4, b: &Mat4) -> Mat4 {
+    let mut m = [[0.0; 4]; 4];
+    for c in 0..4 {
+        for r in 0..4 {
+            m[c][r] = (0..4).map(|k| a[k][r] * b[c][k]).sum();
+        }
+    }
+    m
+}
+
+pub fn transform_point(m: &Mat4, p: [f32; 3]) -> [f32; 3] {
+    let mut r = [0.0; 3];
+    for (i, r) in r.iter_mut().enumerate() {
+        *r = m[0][i] * p[0] + m[1][i] * p[1] + m[2][i] * p[2] + m[3][i];
+    }
+    r
+}
+
+pub fn transform_vector(m: &Mat4, v: [f32; 3]) -> [f32; 3] {
+    let mut r = [0.0; 3];
+    for (i, r) in r.iter_mut().enumerate() {
+        *r = m[0][i] * v[0] + m[1][i] * v[1] + m[2][i] * v[2];
+    }
+    r
+}
+
+/// Compose translation, rotation (unit quaternion `[x, y, z, w]`) and scale into `T * R * S`.
+pub fn from_trs(translation: [f32; 3], rotation: [f32; 4], scale: [f32; 3]) -> Mat4 {
+    let [x, y, z, w] = rotation;
+    let (x2, y2, z2) = (x + x, y + y, z + z);
+    let (xx, xy, xz) = (x * x2, x * y2, x * z2);
+    let (yy, yz, zz) = (y * y2, y * z2, z * z2);
+    let (wx, wy, wz) = (w * x2, w * y2, w * z2);
+
+    [
+        [
+            (1.0 - (yy + zz)) * scale[0],
+            (xy + wz) * scale[0],
+            (xz - wy) * scale[0],
+            0.0,
+        ],
+        [
+            (xy - wz) * scale[1],
+            (1.0 - (xx + zz)) * scale[1],
+            (yz + wx) * scale[1],
+            0.0,
+        ],
+        [
+            (xz + wy) * scale[2],
+            (yz - wx) * scale[2],
+            (1.0 - (xx + yy)) * scale[2],
+            0.0,
+        ],
+        [translation[0], translation[1], translation[2], 1.0],
+    ]
+}
+
+pub fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+pub fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+/// Normalize the vector, zero vectors are returned unchanged.
+pub fn normalize(a: [f32; 3]) -> [f32; 3] {
+    let len = dot(a, a).sqrt();
+    if len > 0.0 {
+        [a[0] / len, a[1] / len, a[2] / len]
+    } else {
+        a
+    }
+}
+
+fn col3(m: &Mat4, c: usize) -> [f32; 3] {
+    [m[c][0], m[c][1], m[c][2]]
+}
+
+/// Determinant of the upper 3x3 part.
+pub fn determinant3(m: &Mat4) -> f32 {
+    dot(col3(m, 0), cross(col3(m, 1), col3(m, 2)))
+}
+
+/// Inverse transpose of the upper 3x3 part for transforming normals.
+pub fn normal_matrix(m: &Mat4) -> Mat4 {
+    let (a0, a1, a2) = (col3(m, 0), col3(m, 1), col3(m, 2));
+    let det = determinant3(m);
+    let inv_det = if det != 0.0 { det.recip() } else { 1.0 };
+
+    let c0 = cross(a1, a2);
+    let c1 = cross(a2, a0);
+    let c2 = cross(a0, a1);
+
+    [
+        [c0[0] * inv_det, c0[1] * inv_det, c0[2] * inv_det, 0.0],
+        [c1[0] * inv_det, c1[1] * inv_det, c1[2] * inv_det, 0.0],
+        [c2[0] * inv_det, c2[1] * inv_det, c2[2] * inv_det, 0.0],
+        [0.0, 0.0, 0.0, 1.0],
+    ]
+}
@@ -0,0 +1,677 @@
+use crate::accessor;
+use crate::gltf::{self, AccessorType, Gltf, Mode};
+use crate::math;
+use crate::tangent;
+
+/// Vertex attributes in order of their shader input location.
+///
+/// Only `POSITION` is required, the others are bound to generated or constant data if missing.
+pub const ATTRIBUTES: [&str; 4] = ["POSITION", "NORMAL", "TEXCOORD_0", "TANGENT"];
+
+/// Shader input location of the optional `COLOR_0` attribute.
+pub const COLOR_LOCATION: u32 = ATTRIBUTES.len() as u32;
+/// Shader input location of the optional `TEXCOORD_1` attribute.
+pub const TEXCOORD_1_LOCATION: u32 = COLOR_LOCATION + 1;
+/// Shader input locations of the optional `JOINTS_0` and `WEIGHTS_0` skinning attributes.
+pub const JOINTS_LOCATION: u32 = TEXCOORD_1_LOCATION + 1;
+pub const WEIGHTS_LOCATION: u32 = JOINTS_LOCATION + 1;
+
+/// Maximum number of morph targets per mesh.
+pub const MAX_MORPH_TARGETS: usize = 64;
+/// Width of the morph target textures, following texels wrap into the next rows.
+const MORPH_TEXTURE_WIDTH: usize = 4096;
+/// Morph target attributes in order of their texels.
+const MORPH_ATTRIBUTES: [&str; 3] = ["POSITION", "NORMAL", "TANGENT"];
+
+pub struct Indices {
+    pub buffer: grr::Buffer,
+    pub ty: grr::IndexTy,
+    pub first: u32,
+    pub count: u32,
+}
+
+pub struct Primitive {
+    pub topology: grr::Primitive,
+    pub vertex_array: grr::VertexArray,
+    pub vertex_buffers: Vec<grr::VertexBufferView>,
+    pub indices: Option<Indices>,
+    pub num_vertices: u32,
+    pub material: Option<usize>,
+    /// Center of the bounding box in object space.
+    pub center: [f32; 3],
+    /// Position, normal and tangent deltas of all morph targets for each vertex.
+    pub morph_targets: Option<grr::ImageView>,
+}
+
+pub struct Mesh {
+    pub primitives: Vec<Primitive>,
+    /// Number of morph targets shared by all primitives.
+    pub num_targets: usize,
+    /// Default morph target weights.
+    pub weights: Vec<f32>,
+}
+
+/// Upload the geometry of all meshes in the glTF asset.
+pub unsafe fn load_meshes(grr: &grr::Device, model: &Gltf) -> anyhow::Result<Vec<Mesh>> {
+    let buffers = model
+        .buffers
+        .iter()
+        .map(|data| grr.create_buffer_from_host(data, grr::MemoryFlags::DEVICE_LOCAL))
+        .collect::<Result<Vec<_>, _>>()?;
+    // Constant attribute value bound with zero stride if vertex colors or UVs are missing.
+    let white = grr.create_buffer_from_host(
+        grr::as_u8_slice(&[1.0f32; 4]),
+        grr::MemoryFlags::DEVICE_LOCAL,
+    )?;
+
+    model
+        .document
+        .meshes
+        .iter()
+        .map(|mesh| {
+            let num_targets = mesh
+                .primitives
+                .first()
+                .map_or(0, |primitive| primitive.targets.len());
+            anyhow::ensure!(
+                mesh.primitives
+                    .iter()
+                    .all(|primitive| primitive.targets.len() == num_targets),
+                "mesh primitives with different number of morph targets"
+            );
+
+            let primitives = mesh
+                .primitives
+                .iter()
+                .map(|primitive| load_primitive(grr, model, &buffers, white, primitive))
+                .collect::<anyhow::Result<_>>()?;
+            Ok(Mesh {
+                primitives,
+                num_targets,
+                weights: mesh.weights.clone(),
+            })
+        })
+        .collect()
+}
+
+unsafe fn vertex_buffer_view(
+    grr: &grr::Device,
+    model: &Gltf,
+    buffers: &[grr::Buffer],
+    accessor: usize,
+) -> anyhow::Result<grr::VertexBufferView> {
+    let accessor = &model.document.accessors[accessor];
+    match accessor.buffer_view {
+        Some(view) if accessor.sparse.is_none() => {
+            // The GPU doesn't bounds check vertex fetches.
+            accessor::view_data(model, accessor, view)?;
+            let view = &model.document.buffer_views[view];
+            Ok(grr::VertexBufferView {
+                buffer: buffers[view.buffer],
+                offset: (view.byte_offset + accessor.byte_offset) as _,
+                stride: view.byte_stride.unwrap_or_else(|| accessor.element_size()) as _,
+                input_rate: grr::InputRate::Vertex,
+            })
+        }
+        // Sparse accessors are materialized on the host first.
+        _ => host_vertex_buffer_view(
+            grr,
+            &accessor::read(model, accessor)?,
+            accessor.element_size(),
+        ),
+    }
+}
+
+unsafe fn host_vertex_buffer_view(
+    grr: &grr::Device,
+    data: &[u8],
+    stride: usize,
+) -> anyhow::Result<grr::VertexBufferView> {
+    Ok(grr::VertexBufferView {
+        buffer: grr.create_buffer_from_host(data, grr::MemoryFlags::DEVICE_LOCAL)?,
+        offset: 0,
+        stride: stride as _,
+        input_rate: grr::InputRate::Vertex,
+    })
+}
+
+/// De-indexed triangle list with face normals for flat shading primitives without `NORMAL`.
+struct Flat {
+    /// Source vertex for each triangle corner.
+    vertices: Vec<u32>,
+    normals: Vec<[f32; 3]>,
+}
+
+impl Flat {
+    fn new(positions: &[[f32; 3]], triangles: Vec<u32>) -> anyhow::Result<Self> {
+        let corners = gather(positions, &triangles)?;
+        let mut normals = Vec::with_capacity(corners.len());
+        for triangle in corners.chunks_exact(3) {
+            let normal = math::normalize(math::cross(
+                math::sub(triangle[1], triangle[0]),
+                math::sub(triangle[2], triangle[0]),
+            ));
+            normals.extend_from_slice(&[normal; 3]);
+        }
+
+        Ok(Flat {
+            vertices: triangles,
+            normals,
+        })
+    }
+}
+
+unsafe fn load_primitive(
+    grr: &grr::Device,
+    model: &Gltf,
+    buffers: &[grr::Buffer],
+    white: grr::Buffer,
+    primitive: &gltf::Primitive,
+) -> anyhow::Result<Primitive> {
+    let position = *primitive
+        .attributes
+        .get("POSITION")
+        .ok_or_else(|| anyhow::anyhow!("primitive without POSITION attribute"))?;
+
+    // Triangles without normals are flat shaded, requiring separate vertices for each face.
+    let flat = match primitive.attributes.get("NORMAL") {
+        Some(_) => None,
+        None => match triangle_list(model, primitive)? {
+            Some(triangles) => Some(Flat::new(&read_vec3(model, position)?, triangles)?),
+            None => None,
+        },
+    };
+
+    // Generated tangents may require splitting vertices along UV seams.
+    let (tangents, split) = match primitive.attributes.get("TANGENT") {
+        Some(_) => (Vec::new(), None),
+        None => generate_tangents(model, primitive, flat.as_ref())?,
+    };
+    // Source vertex of each vertex if the vertices are rebuilt on the host.
+    let remap = match (&flat, &split) {
+        (Some(flat), _) => Some(&flat.vertices[..]),
+        (None, Some(split)) => Some(&split.vertices[..]),
+        (None, None) => None,
+    };
+
+    let attribute_view = |attribute: usize| -> anyhow::Result<_> {
+        let accessor = &model.document.accessors[attribute];
+        let format = accessor::vertex_format(accessor)?;
+        let view = match remap {
+            Some(vertices) => {
+                let size = accessor.element_size();
+                let data = accessor::read(model, accessor)?;
+                let elements = data.chunks_exact(size).collect::<Vec<_>>();
+                let data = gather(&elements, vertices)?.concat();
+                host_vertex_buffer_view(grr, &data, size)?
+            }
+            None => {
+                anyhow::ensure!(
+                    accessor.count >= model.document.accessors[position].count,
+                    "vertex attribute with fewer elements than POSITION"
+                );
+                vertex_buffer_view(grr, model, buffers, attribute)?
+            }
+        };
+        Ok((format, view))
+    };
+    // Constant attribute value with zero stride.
+    let constant_view = grr::VertexBufferView {
+        buffer: white,
+        offset: 0,
+        stride: 0,
+        input_rate: grr::InputRate::Vertex,
+    };
+
+    let mut vertex_attributes = Vec::new();
+    let mut vertex_buffers = Vec::new();
+    for (location, name) in ATTRIBUTES.iter().enumerate() {
+        let (format, view) = match primitive.attributes.get(*name) {
+            Some(&attribute) => attribute_view(attribute)?,
+            None if *name == "NORMAL" => match flat {
+                Some(ref flat) => (
+                    grr::VertexFormat::Xyz32Float,
+                    host_vertex_buffer_view(
+                        grr,
+                        grr::as_u8_slice(&flat.normals),
+                        std::mem::size_of::<[f32; 3]>(),
+                    )?,
+                ),
+                // Points and lines aren't shaded with face normals.
+                None => (grr::VertexFormat::Xyz32Float, constant_view),
+            },
+            None if *name == "TANGENT" => {
+                let view = host_vertex_buffer_view(
+                    grr,
+                    grr::as_u8_slice(&tangents),
+                    std::mem::size_of::<[f32; 4]>(),
+                )?;
+                (grr::VertexFormat::Xyzw32Float, view)
+            }
+            None if *name == "TEXCOORD_0" => (grr::VertexFormat::Xy32Float, constant_view),
+            None => anyhow::bail!("primitive without {} attribute", name),
+        };
+        vertex_attributes.push(grr::VertexAttributeDesc {
+            location: location as _,
+            binding: location as _,
+            format,
+            offset: 0,
+        });
+        vertex_buffers.push(view);
+    }
+    let (format, view) = match primitive.attributes.get("COLOR_0") {
+        Some(&attribute) => {
+            let accessor = &model.document.accessors[attribute];
+            anyhow::ensure!(
+                accessor.ty == AccessorType::Vec3 || accessor.ty == AccessorType::Vec4,
+                "COLOR_0 must be vec3 or vec4"
+            );
+            attribute_view(attribute)?
+        }
+        None => (grr::VertexFormat::Xyzw32Float, constant_view),
+    };
+    vertex_attributes.push(grr::VertexAttributeDesc {
+        location: COLOR_LOCATION,
+        binding: COLOR_LOCATION,
+        format,
+        offset: 0,
+    });
+    vertex_buffers.push(view);
+    // Without a second UV set, fall back to the first one.
+    let texcoord_1 = primitive
+        .attributes
+        .get("TEXCOORD_1")
+        .or_else(|| primitive.attributes.get("TEXCOORD_0"));
+    let (format, view) = match texcoord_1 {
+        Some(&attribute) => attribute_view(attribute)?,
+        None => (grr::VertexFormat::Xy32Float, constant_view),
+    };
+    vertex_attributes.push(grr::VertexAttributeDesc {
+        location: TEXCOORD_1_LOCATION,
+        binding: TEXCOORD_1_LOCATION,
+        format,
+        offset: 0,
+    });
+    vertex_buffers.push(view);
+    // Skinning attributes are only read for instances with a skin.
+    for &(location, name) in &[
+        (JOINTS_LOCATION, "JOINTS_0"),
+        (WEIGHTS_LOCATION, "WEIGHTS_0"),
+    ] {
+        let (format, view) = match primitive.attributes.get(name) {
+            Some(&attribute) => {
+                anyhow::ensure!(
+                    model.document.accessors[attribute].ty == AccessorType::Vec4,
+                    "{} must be vec4",
+                    name
+                );
+                attribute_view(attribute)?
+            }
+            None => (grr::VertexFormat::Xyzw32Float, constant_view),
+        };
+        vertex_attributes.push(grr::VertexAttributeDesc {
+            location,
+            binding: location,
+            format,
+            offset: 0,
+        });
+        vertex_buffers.push(view);
+    }
+
+    let vertex_array = grr.create_vertex_array(&vertex_attributes)?;
+
+    let morph_targets = load_morph_targets(grr, model, primitive, remap)?;
+
+    let positions = &model.document.accessors[position];
+    let center = match (&positions.min, &positions.max) {
+        (Some(min), Some(max)) if min.len() == 3 && max.len() == 3 => [
+            0.5 * (min[0] + max[0]),
+            0.5 * (min[1] + max[1]),
+            0.5 * (min[2] + max[2]),
+        ],
+        _ => [0.0; 3],
+    };
+
+    if let Some(flat) = flat {
+        return Ok(Primitive {
+            topology: grr::Primitive::Triangles,
+            vertex_array,
+            vertex_buffers,
+            indices: None,
+            num_vertices: flat.vertices.len() as _,
+            material: primitive.material,
+            center,
+            morph_targets,
+        });
+    }
+    if let Some(split) = split {
+        let indices = split.triangles;
+        return Ok(Primitive {
+            topology: grr::Primitive::Triangles,
+            vertex_array,
+            vertex_buffers,
+            indices: Some(Indices {
+                buffer: grr.create_buffer_from_host(
+                    grr::as_u8_slice(&indices),
+                    grr::MemoryFlags::DEVICE_LOCAL,
+                )?,
+                ty: grr::IndexTy::U32,
+                first: 0,
+                count: indices.len() as _,
+            }),
+            num_vertices: split.vertices.len() as _,
+            material: primitive.material,
+            center,
+            morph_targets,
+        });
+    }
+
+    let num_vertices = positions.count as u32;
+    let topology = match primitive.mode {
+        Mode::Points => grr::Primitive::Points,
+        Mode::Lines | Mode::LineLoop => grr::Primitive::Lines,
+        Mode::LineStrip => grr::Primitive::LineStrip,
+        Mode::Triangles | Mode::TriangleFan => grr::Primitive::Triangles,
+        Mode::TriangleStrip => grr::Primitive::TriangleStrip,
+    };
+
+    let indices = match primitive.mode {
+        // Line loops and triangle fans are unrolled into line and triangle lists.
+        Mode::LineLoop | Mode::TriangleFan => {
+            let vertices = match primitive.indices {
+                Some(indices) => accessor::read_indices(model, indices)?,
+                None => (0..num_vertices).collect(),
+            };
+            check_indices(&vertices, num_vertices)?;
+            let indices = match primitive.mode {
+                Mode::LineLoop => unroll_line_loop(&vertices),
+                _ => unroll_triangle_fan(&vertices),
+            };
+            Some(Indices {
+                buffer: grr.create_buffer_from_host(
+                    grr::as_u8_slice(&indices),
+                    grr::MemoryFlags::DEVICE_LOCAL,
+                )?,
+                ty: grr::IndexTy::U32,
+                first: 0,
+                count: indices.len() as _,
+            })
+        }
+        _ => match primitive.indices {
+            Some(indices) => {
+                let accessor = &model.document.accessors[indices];
+                check_indices(&accessor::read_indices(model, indices)?, num_vertices)?;
+                let view = vertex_buffer_view(grr, model, buffers, indices)?;
+                anyhow::ensure!(
+                    view.stride as usize == accessor.element_size()
+                        && view.offset as usize % accessor.element_size() == 0,
+                    "index accessors must be tightly packed and aligned"
+                );
+                Some(Indices {
+                    buffer: view.buffer,
+                    ty: accessor::index_ty(accessor)?,
+                    first: (view.offset as usize / accessor.element_size()) as _,
+                    count: accessor.count as _,
+                })
+            }
+            None => None,
+        },
+    };
+
+    Ok(Primitive {
+        topology,
+        vertex_array,
+        vertex_buffers,
+        indices,
+        num_vertices,
+        material: primitive.material,
+        center,
+        morph_targets,
+    })
+}
+
+/// Indices are drawn directly, out of bounds vertices must be rejected up front.
+fn check_indices(indices: &[u32], num_vertices: u32) -> anyhow::Result<()> {
+    match indices.iter().find(|&&index| index >= num_vertices) {
+        Some(index) => anyhow::bail!("vertex index {} out of bounds ({})", index, num_vertices),
+        None => Ok(()),
+    }
+}
+
+/// Upload the morph target deltas into a float texture.
+///
+/// Each vertex occupies `3 * targets` consecutive texels, storing the position, normal
+/// and tangent delta of each target. Missing attributes are filled with zeros.
+/// `remap` selects the source vertex of each vertex if rebuilt on the host.
+unsafe fn load_morph_targets(
+    grr: &grr::Device,
+    model: &Gltf,
+    primitive: &gltf::Primitive,
+    remap: Option<&[u32]>,
+) -> anyhow::Result<Option<grr::ImageView>> {
+    let targets = &primitive.targets;
+    if targets.is_empty() {
+        return Ok(None);
+    }
+    anyhow::ensure!(
+        targets.len() <= MAX_MORPH_TARGETS,
+        "{} morph targets exceed the supported maximum of {}",
+        targets.len(),
+        MAX_MORPH_TARGETS
+    );
+
+    let num_vertices = match remap {
+        Some(vertices) => vertices.len(),
+        None => model.document.accessors[primitive.attributes["POSITION"]].count,
+    };
+    let texels_per_vertex = MORPH_ATTRIBUTES.len() * targets.len();
+    let mut texels = vec![[0.0f32; 4]; num_vertices * texels_per_vertex];
+    for (t, target) in targets.iter().enumerate() {
+        for (a, name) in MORPH_ATTRIBUTES.iter().enumerate() {
+            let attribute = match target.get(*name) {
+                Some(&attribute) => attribute,
+                None => continue,
+            };
+            let deltas = read_vec3(model, attribute)?;
+            let deltas = match remap {
+                Some(vertices) => gather(&deltas, vertices)?,
+                None => deltas,
+            };
+            anyhow::ensure!(
+                deltas.len() == num_vertices,
+                "morph target {} doesn't match the vertex count",
+                name
+            );
+            for (v, [x, y, z]) in deltas.into_iter().enumerate() {
+                texels[v * texels_per_vertex + MORPH_ATTRIBUTES.len() * t + a] = [x, y, z, 0.0];
+            }
+        }
+    }
+
+    let width = MORPH_TEXTURE_WIDTH as u32;
+    let height = ((texels.len() + MORPH_TEXTURE_WIDTH - 1) / MORPH_TEXTURE_WIDTH) as u32;
+    texels.resize((width * height) as usize, [0.0; 4]);
+
+    let image = grr.create_image(
+        grr::ImageType::D2 {
+            width,
+            height,
+            layers: 1,
+            samples: 1,
+        },
+        grr::Format::R32G32B32A32_SFLOAT,
+        1,
+    )?;
+    grr.copy_host_to_image(
+        grr::as_u8_slice(&texels),
+        image,
+        grr::HostImageCopy {
+            host_layout: grr::MemoryLayout {
+                base_format: grr::BaseFormat::RGBA,
+                format_layout: grr::FormatLayout::F32,
+                row_length: width,
+                image_height: height,
+                alignment: 4,
+            },
+            image_subresource: grr::SubresourceLayers {
+                level: 0,
+                layers: 0..1,
+            },
+            image_offset: grr::Offset { x: 0, y: 0, z: 0 },
+            image_extent: grr::Extent {
+                width,
+                height,
+                depth: 1,
+            },
+        },
+    );
+
+    Ok(Some(image.as_view()))
+}
+
+/// Generate MikkTSpace tangents for primitives shipping without `TANGENT` attribute.
+///
+/// Returns the vertex split if vertices need different tangents for their triangles.
+fn generate_tangents(
+    model: &Gltf,
+    primitive: &gltf::Primitive,
+    flat: Option<&Flat>,
+) -> anyhow::Result<(Vec<[f32; 4]>, Option<tangent::Split>)> {
+    // Tangents follow the UV set of the normal texture.
+    let tex_coord = primitive
+        .material
+        .and_then(|material| model.document.materials[material].normal_texture.as_ref())
+        .map_or(0, |info| {
+            info.extensions
+                .texture_transform
+                .as_ref()
+                .and_then(|transform| transform.tex_coord)
+                .unwrap_or(info.tex_coord)
+        });
+    let texcoord = primitive
+        .attributes
+        .get(&format!("TEXCOORD_{}", tex_coord))
+        .or_else(|| primitive.attributes.get("TEXCOORD_0"));
+
+    let positions = read_vec3(model, primitive.attributes["POSITION"])?;
+    let texcoords = match texcoord {
+        Some(&texcoord) => Some(read_vec2(model, texcoord)?),
+        None => None,
+    };
+
+    if let Some(flat) = flat {
+        return match texcoords {
+            Some(texcoords) => {
+                let triangles = (0..flat.vertices.len() as u32).collect::<Vec<_>>();
+                // Flat vertices aren't shared between triangles and never split.
+                let (tangents, _) = tangent::generate(
+                    &gather(&positions, &flat.vertices)?,
+                    &flat.normals,
+                    &gather(&texcoords, &flat.vertices)?,
+                    &triangles,
+                )?;
+                Ok((tangents, None))
+            }
+            None => Ok((
+                flat.normals
+                    .iter()
+                    .map(|&n| tangent::orthonormal(n))
+                    .collect(),
+                None,
+            )),
+        };
+    }
+
+    match triangle_list(model, primitive)? {
+        Some(triangles) => {
+            let normals = read_vec3(model, primitive.attributes["NORMAL"])?;
+            match texcoords {
+                Some(texcoords) => tangent::generate(&positions, &normals, &texcoords, &triangles),
+                None => Ok((
+                    normals.iter().map(|&n| tangent::orthonormal(n)).collect(),
+                    None,
+                )),
+            }
+        }
+        // Points and lines aren't normal mapped.
+        None => Ok((vec![[0.0; 4]; positions.len()], None)),
+    }
+}
+
+fn read_vec3(model: &Gltf, attribute: usize) -> anyhow::Result<Vec<[f32; 3]>> {
+    Ok(
+        accessor::read_f32(model, &model.document.accessors[attribute])?
+            .chunks_exact(3)
+            .map(|v| [v[0], v[1], v[2]])
+            .collect(),
+    )
+}
+
+fn read_vec2(model: &Gltf, attribute: usize) -> anyhow::Result<Vec<[f32; 2]>> {
+    Ok(
+        accessor::read_f32(model, &model.document.accessors[attribute])?
+            .chunks_exact(2)
+            .map(|v| [v[0], v[1]])
+            .collect(),
+    )
+}
+
+/// Select the vertex elements in the given order.
+fn gather<T: Copy>(data: &[T], vertices: &[u32]) -> anyhow::Result<Vec<T>> {
+    vertices
+        .iter()
+        .map(|&vertex| {
+            data.get(vertex as usize)
+                .copied()
+                .ok_or_else(|| anyhow::anyhow!("vertex index {} out of bounds", vertex))
+        })
+        .collect()
+}
+
+/// Vertex indices of the primitive as triangle list, `None` for points and lines.
+fn triangle_list(model: &Gltf, primitive: &gltf::Primitive) -> anyhow::Result<Option<Vec<u32>>> {
+    let num_vertices = model.document.accessors[primitive.attributes["POSITION"]].count as u32;
+    let vertices = match primitive.indices {
+        Some(indices) => accessor::read_indices(model, indices)?,
+        None => (0..num_vertices).collect(),
+    };
+    match primitive.mode {
+        Mode::Triangles => {
+            let mut triangles = vertices;
+            triangles.truncate(triangles.len() / 3 * 3);
+            Ok(Some(triangles))
+        }
+        Mode::TriangleStrip => Ok(Some(unroll_triangle_strip(&vertices))),
+        Mode::TriangleFan => Ok(Some(unroll_triangle_fan(&vertices))),
+        Mode::Points | Mode::Lines | Mode::LineLoop | Mode::LineStrip => Ok(None),
+    }
+}
+
+fn unroll_line_loop(vertices: &[u32]) -> Vec<u32> {
+    let mut indices = Vec::with_capacity(2 * vertices.len());
+    for i in 0..vertices.len() {
+        indices.extend_from_slice(&[vertices[i], vertices[(i + 1) % vertices.len()]]);
+    }
+    indices
+}
+
+fn unroll_triangle_fan(vertices: &[u32]) -> Vec<u32> {
+    let mut indices = Vec::with_capacity(3 * vertices.len());
+    for i in 1..vertices.len().saturating_sub(1) {
+        indices.extend_from_slice(&[vertices[0], vertices[i], vertices[i + 1]]);
+    }
+    indices
+}
+
+fn unroll_triangle_strip(vertices: &[u32]) -> Vec<u32> {
+    let mut indices = Vec::with_capacity(3 * vertices.len());
+    for i in 0..vertices.len().saturating_sub(2) {
+        // Every second triangle is flipped to keep a consistent winding order.
+        if i % 2 == 0 {
+            indices.extend_from_slice(&[vertices[i], vertices[i + 1], vertices[i + 2]]);
+        } else {
+            indices.extend_from_slice(&[vertices[i + 1], vertices[i], vertices[i + 2]]);
+        }
+    }
+    indices
+}
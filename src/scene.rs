@@ -0,0 +1,160 @@
+use crate::gltf::Document;
+use crate::math::{self, Mat4};
+
+#[derive(Debug, Copy, Clone)]
+pub enum Transform {
+    Matrix(Mat4),
+    Trs {
+        translation: [f32; 3],
+        rotation: [f32; 4],
+        scale: [f32; 3],
+    },
+}
+
+impl Transform {
+    pub fn matrix(&self) -> Mat4 {
+        match *self {
+            Transform::Matrix(m) => m,
+            Transform::Trs {
+                translation,
+                rotation,
+                scale,
+            } => math::from_trs(translation, rotation, scale),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Node {
+    pub transform: Transform,
+    pub children: Vec<usize>,
+    pub mesh: Option<usize>,
+    pub skin: Option<usize>,
+    /// Morph target weights, overriding the mesh defaults if not empty.
+    pub weights: Vec<f32>,
+    pub light: Option<usize>,
+}
+
+/// Mesh placed in the world by a node.
+#[derive(Debug, Copy, Clone)]
+pub struct Instance {
+    pub node: usize,
+    pub mesh: usize,
+    pub skin: Option<usize>,
+    pub model_to_world: Mat4,
+}
+
+/// Punctual light placed in the world by a node.
+#[derive(Debug, Copy, Clone)]
+pub struct LightInstance {
+    pub light: usize,
+    pub light_to_world: Mat4,
+}
+
+/// Node hierarchy of the active glTF scene.
+pub struct Scene {
+    pub roots: Vec<usize>,
+    pub nodes: Vec<Node>,
+}
+
+impl Scene {
+    pub fn new(document: &Document) -> Self {
+        let nodes = document
+            .nodes
+            .iter()
+            .map(|node| Node {
+                transform: match node.matrix {
+                    Some(ref m) => Transform::Matrix(math::from_cols_array(m)),
+                    None => Transform::Trs {
+                        translation: node.translation.unwrap_or([0.0; 3]),
+                        rotation: node.rotation.unwrap_or([0.0, 0.0, 0.0, 1.0]),
+                        scale: node.scale.unwrap_or([1.0; 3]),
+                    },
+                },
+                children: node.children.clone(),
+                mesh: node.mesh,
+                skin: node.skin,
+                weights: node.weights.clone(),
+                light: node.extensions.light.as_ref().map(|light| light.light),
+            })
+            .collect();
+
+        let roots = match document.scenes.get(document.scene.unwrap_or(0)) {
+            Some(scene) => scene.nodes.clone(),
+            // Without scenes, fall back to every node which isn't a child of another one.
+            None => {
+                let mut is_root = vec![true; document.nodes.len()];
+                for node in &document.nodes {
+                    for &child in &node.children {
+                        is_root[child] = false;
+                    }
+                }
+                (0..document.nodes.len()).filter(|&i| is_root[i]).collect()
+            }
+        };
+
+        Scene { roots, nodes }
+    }
+
+    /// Compose the local transforms down the hierarchy into world transforms for each node.
+    pub fn world_transforms(&self) -> Vec<Mat4> {
+        let mut transforms = vec![math::IDENTITY; self.nodes.len()];
+        let mut stack = self
+            .roots
+            .iter()
+            .map(|&root| (root, math::IDENTITY))
+            .collect::<Vec<_>>();
+        while let Some((node, parent_to_world)) = stack.pop() {
+            let local_to_world = math::mul(&parent_to_world, &self.nodes[node].transform.matrix());
+            transforms[node] = local_to_world;
+            stack.extend(
+                self.nodes[node]
+                    .children
+                    .iter()
+                    .map(|&child| (child, local_to_world)),
+            );
+        }
+        transforms
+    }
+
+    /// All mesh instances of the scene with their world transforms.
+    pub fn instances(&self) -> Vec<Instance> {
+        let transforms = self.world_transforms();
+        let mut instances = Vec::new();
+        let mut stack = self.roots.clone();
+        while let Some(node) = stack.pop() {
+            if let Some(mesh) = self.nodes[node].mesh {
+                let skin = self.nodes[node].skin;
+                instances.push(Instance {
+                    node,
+                    mesh,
+                    skin,
+                    // Skinned meshes are placed by their joints only.
+                    model_to_world: match skin {
+                        Some(_) => math::IDENTITY,
+                        None => transforms[node],
+                    },
+                });
+            }
+            stack.extend_from_slice(&self.nodes[node].children);
+        }
+        instances
+    }
+
+    /// All punctual lights of the scene with their world transforms.
+    pub fn lights(&self) -> Vec<LightInstance> {
+        let transforms = self.world_transforms();
+        let mut lights = Vec::new();
+        let mut stack = self.roots.clone();
+        while let Some(node) = stack.pop() {
+            if let Some(light) = self.nodes[node].light {
+                lights.push(LightInstance {
+                    light,
+                    light_to_world: transforms[node],
+                });
+            }
+            stack.extend_from_slice(&self.nodes[node].children);
+        }
+        lights
+    }
+}
@@ -0,0 +1,75 @@
+use crate::accessor;
+use crate::gltf::{self, AccessorType, ComponentType, Gltf};
+use crate::math::{self, Mat4};
+
+/// Maximum number of joints per skin, limited by the uniform block size.
+pub const MAX_JOINTS: usize = 128;
+
+pub struct Skin {
+    pub joints: Vec<usize>,
+    pub inverse_bind_matrices: Vec<Mat4>,
+}
+
+impl Skin {
+    pub fn new(model: &Gltf, skin: &gltf::Skin) -> anyhow::Result<Self> {
+        anyhow::ensure!(
+            skin.joints.len() <= MAX_JOINTS,
+            "skin with {} joints exceeds the supported maximum of {}",
+            skin.joints.len(),
+            MAX_JOINTS
+        );
+        anyhow::ensure!(
+            skin.joints
+                .iter()
+                .all(|&joint| joint < model.document.nodes.len()),
+            "skin joint out of node bounds"
+        );
+
+        let inverse_bind_matrices = match skin.inverse_bind_matrices {
+            Some(accessor) => {
+                let accessor = &model.document.accessors[accessor];
+                anyhow::ensure!(
+                    accessor.ty == AccessorType::Mat4
+                        && accessor.component_type == ComponentType::F32,
+                    "inverse bind matrices must be float mat4"
+                );
+                anyhow::ensure!(
+                    accessor.count >= skin.joints.len(),
+                    "fewer inverse bind matrices than joints"
+                );
+                accessor::read_f32(model, accessor)?
+                    .chunks_exact(16)
+                    .map(|m| {
+                        let mut cols = [0.0; 16];
+                        cols.copy_from_slice(m);
+                        math::from_cols_array(&cols)
+                    })
+                    .collect()
+            }
+            None => vec![math::IDENTITY; skin.joints.len()],
+        };
+
+        Ok(Skin {
+            joints: skin.joints.clone(),
+            inverse_bind_matrices,
+        })
+    }
+
+    /// Transformations of each joint from bind pose into world space.
+    pub fn joint_matrices(&self, world_transforms: &[Mat4]) -> Vec<Mat4> {
+        self.joints
+            .iter()
+            .zip(&self.inverse_bind_matrices)
+            .map(|(&joint, inverse_bind)| math::mul(&world_transforms[joint], inverse_bind))
+            .collect()
+    }
+}
+
+pub fn load_skins(model: &Gltf) -> anyhow::Result<Vec<Skin>> {
+    model
+        .document
+        .skins
+        .iter()
+        .map(|skin| Skin::new(model, skin))
+        .collect()
+}
@@ -0,0 +1,290 @@
+use crate::math;
+use std::collections::HashMap;
+
+/// Triangle mesh view for generating MikkTSpace tangents.
+struct Geometry<'a> {
+    positions: &'a [[f32; 3]],
+    normals: &'a [[f32; 3]],
+    texcoords: &'a [[f32; 2]],
+    triangles: &'a [u32],
+    /// Tangent of each triangle corner.
+    tangents: Vec<[f32; 4]>,
+}
+
+impl Geometry<'_> {
+    fn vertex(&self, face: usize, vert: usize) -> usize {
+        self.triangles[3 * face + vert] as usize
+    }
+}
+
+impl mikktspace::Geometry for Geometry<'_> {
+    fn num_faces(&self) -> usize {
+        self.triangles.len() / 3
+    }
+
+    fn num_vertices_of_face(&self, _face: usize) -> usize {
+        3
+    }
+
+    fn position(&self, face: usize, vert: usize) -> [f32; 3] {
+        self.positions[self.vertex(face, vert)]
+    }
+
+    fn normal(&self, face: usize, vert: usize) -> [f32; 3] {
+        self.normals[self.vertex(face, vert)]
+    }
+
+    fn tex_coord(&self, face: usize, vert: usize) -> [f32; 2] {
+        self.texcoords[self.vertex(face, vert)]
+    }
+
+    fn set_tangent_encoded(&mut self, tangent: [f32; 4], face: usize, vert: usize) {
+        self.tangents[3 * face + vert] = tangent;
+    }
+}
+
+/// Generate MikkTSpace tangents for each corner of a triangle list.
+fn generate_corners(
+    positions: &[[f32; 3]],
+    normals: &[[f32; 3]],
+    texcoords: &[[f32; 2]],
+    triangles: &[u32],
+) -> anyhow::Result<Vec<[f32; 4]>> {
+    anyhow::ensure!(
+        normals.len() == positions.len() && texcoords.len() == positions.len(),
+        "vertex attributes with mismatching counts"
+    );
+    anyhow::ensure!(
+        triangles.iter().all(|&i| (i as usize) < positions.len()),
+        "triangle index out of vertex bounds"
+    );
+
+    let mut geometry = Geometry {
+        positions,
+        normals,
+        texcoords,
+        triangles,
+        tangents: vec![[0.0; 4]; triangles.len()],
+    };
+    anyhow::ensure!(
+        mikktspace::generate_tangents(&mut geometry),
+        "failed to generate tangents"
+    );
+    Ok(geometry.tangents)
+}
+
+/// Vertices duplicated because MikkTSpace assigned different tangents to their corners.
+pub struct Split {
+    /// Source vertex of each vertex, the input vertices followed by the duplicates.
+    pub vertices: Vec<u32>,
+    /// Triangle list referencing the split vertices.
+    pub triangles: Vec<u32>,
+}
+
+/// Generate per-vertex tangents (`w` denoting the bitangent sign) for a triangle list.
+///
+/// MikkTSpace computes tangents per triangle corner and may assign different tangents
+/// to corners sharing a vertex, e.g. along mirrored UV seams. These vertices are split,
+/// returning the tangents of the split vertices together with the remapping if any.
+/// Vertices not referenced by any triangle get a zero tangent.
+pub fn generate(
+    positions: &[[f32; 3]],
+    normals: &[[f32; 3]],
+    texcoords: &[[f32; 2]],
+    triangles: &[u32],
+) -> anyhow::Result<(Vec<[f32; 4]>, Option<Split>)> {
+    let corners = generate_corners(positions, normals, texcoords, triangles)?;
+
+    // Corners of the same vertex only share bitwise identical tangents.
+    let bits = |[x, y, z, w]: [f32; 4]| [x.to_bits(), y.to_bits(), z.to_bits(), w.to_bits()];
+    let mut tangents = vec![None; positions.len()];
+    let mut vertices = (0..positions.len() as u32).collect::<Vec<_>>();
+    let mut split_triangles = Vec::with_capacity(triangles.len());
+    // Duplicated vertices by source vertex and tangent.
+    let mut duplicates = HashMap::new();
+    for (&vertex, &corner) in triangles.iter().zip(&corners) {
+        let index = match tangents[vertex as usize] {
+            None => {
+                tangents[vertex as usize] = Some(corner);
+                vertex
+            }
+            Some(tangent) if bits(tangent) == bits(corner) => vertex,
+            Some(_) => *duplicates.entry((vertex, bits(corner))).or_insert_with(|| {
+                vertices.push(vertex);
+                tangents.push(Some(corner));
+                vertices.len() as u32 - 1
+            }),
+        };
+        split_triangles.push(index);
+    }
+
+    let tangents = tangents
+        .into_iter()
+        .map(|tangent| tangent.unwrap_or([0.0; 4]))
+        .collect();
+    let split = if vertices.len() > positions.len() {
+        Some(Split {
+            vertices,
+            triangles: split_triangles,
+        })
+    } else {
+        None
+    };
+    Ok((tangents, split))
+}
+
+/// Arbitrary tangent perpendicular to the normal, for surfaces without UVs to derive it from.
+pub fn orthonormal(normal: [f32; 3]) -> [f32; 4] {
+    // Project the axis least aligned with the normal onto the tangent plane.
+    let axis = if normal[0].abs() < 0.9 {
+        [1.0, 0.0, 0.0]
+    } else {
+        [0.0, 1.0, 0.0]
+    };
+    let [x, y, z] = math::normalize(math::cross(math::cross(normal, axis), normal));
+    [x, y, z, 1.0]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accessor;
+    use crate::gltf::Gltf;
+    use std::path::Path;
+
+    fn dot(a: [f32; 4], b: [f32; 4]) -> f32 {
+        a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+    }
+
+    #[test]
+    fn quad() {
+        let positions = [
+            [0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [1.0, 1.0, 0.0],
+            [0.0, 1.0, 0.0],
+        ];
+        let normals = [[0.0, 0.0, 1.0]; 4];
+        let texcoords = [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]];
+        let triangles = [0, 1, 2, 0, 2, 3];
+
+        let (tangents, split) = generate(&positions, &normals, &texcoords, &triangles).unwrap();
+        assert!(split.is_none());
+        for tangent in tangents {
+            assert!(dot(tangent, [1.0, 0.0, 0.0, 0.0]) > 0.999);
+            assert!(tangent[3] > 0.0);
+        }
+    }
+
+    #[test]
+    fn orthonormal_tangent() {
+        for &normal in &[
+            [1.0, 0.0, 0.0],
+            [0.0, -1.0, 0.0],
+            math::normalize([1.0, 2.0, 3.0]),
+        ] {
+            let tangent = orthonormal(normal);
+            let [x, y, z] = normal;
+            assert!(dot(tangent, [x, y, z, 0.0]).abs() < 1e-6);
+            assert!((dot(tangent, tangent) - 1.0).abs() < 1e-6);
+        }
+    }
+
+    /// Compare generated tangents against the authored ones of all primitives.
+    ///
+    /// Exporters split vertices whose tangents differ, these are welded first to check
+    /// that generating tangents splits exactly the same vertices.
+    fn compare_authored(path: &Path) {
+        let model = Gltf::open(path).unwrap();
+        let read = |attribute: usize, n: usize| {
+            accessor::read_f32(&model, &model.document.accessors[attribute])
+                .unwrap()
+                .chunks_exact(n)
+                .map(|v| v.to_vec())
+                .collect::<Vec<_>>()
+        };
+
+        for primitive in model
+            .document
+            .meshes
+            .iter()
+            .flat_map(|mesh| &mesh.primitives)
+        {
+            let attributes = &primitive.attributes;
+            let positions = read(attributes["POSITION"], 3);
+            let normals = read(attributes["NORMAL"], 3);
+            let texcoords = read(attributes["TEXCOORD_0"], 2);
+            let expected = read(attributes["TANGENT"], 4)
+                .iter()
+                .map(|v| [v[0], v[1], v[2], v[3]])
+                .collect::<Vec<_>>();
+            let authored = accessor::read_indices(&model, primitive.indices.unwrap()).unwrap();
+
+            // Weld vertices only differing in their tangent.
+            let mut welded = HashMap::new();
+            let mut sources = Vec::new();
+            let vertices = (0..positions.len())
+                .map(|v| {
+                    let key = [&positions[v], &normals[v], &texcoords[v]]
+                        .iter()
+                        .flat_map(|attribute| attribute.iter().map(|x| x.to_bits()))
+                        .collect::<Vec<_>>();
+                    *welded.entry(key).or_insert_with(|| {
+                        sources.push(v);
+                        sources.len() as u32 - 1
+                    })
+                })
+                .collect::<Vec<_>>();
+            let triangles = authored
+                .iter()
+                .map(|&v| vertices[v as usize])
+                .collect::<Vec<_>>();
+            let positions = sources
+                .iter()
+                .map(|&v| [positions[v][0], positions[v][1], positions[v][2]])
+                .collect::<Vec<_>>();
+            let normals = sources
+                .iter()
+                .map(|&v| [normals[v][0], normals[v][1], normals[v][2]])
+                .collect::<Vec<_>>();
+            let texcoords = sources
+                .iter()
+                .map(|&v| [texcoords[v][0], texcoords[v][1]])
+                .collect::<Vec<_>>();
+
+            let (tangents, split) = generate(&positions, &normals, &texcoords, &triangles).unwrap();
+            let triangles = split.map_or(triangles, |split| split.triangles);
+            assert_eq!(tangents.len(), expected.len());
+
+            // Each generated vertex corresponds to exactly one authored vertex.
+            let mut matching = vec![None; tangents.len()];
+            for (&vertex, &authored) in triangles.iter().zip(&authored) {
+                let (vertex, authored) = (vertex as usize, authored as usize);
+                assert_eq!(*matching[vertex].get_or_insert(authored), authored);
+                let (tangent, expected) = (tangents[vertex], expected[authored]);
+                assert!(
+                    dot(tangent, expected) > 0.9999,
+                    "{:?} {:?}",
+                    tangent,
+                    expected
+                );
+                assert!(tangent[3] * expected[3] > 0.0);
+            }
+        }
+    }
+
+    /// Mirrored UVs require splitting the vertices along the seam, see README in fixtures.
+    #[test]
+    fn mirrored_uv() {
+        compare_authored(
+            &Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/mirrored_uv.gltf"),
+        );
+    }
+
+    /// Requires SciFiHelmet in `assets` (see README), run with `cargo test -- --ignored`.
+    #[test]
+    #[ignore]
+    fn scifi_helmet() {
+        compare_authored(&Path::new(env!("CARGO_MANIFEST_DIR")).join("assets/SciFiHelmet.gltf"));
+    }
+}
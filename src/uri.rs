@@ -0,0 +1,75 @@
+use anyhow::Context;
+use percent_encoding::percent_decode_str;
+use std::fs;
+use std::path::Path;
+
+/// Load the data referenced by a glTF `uri`.
+///
+/// Supports embedded `data:` URIs (base64 or percent-encoded) and relative
+/// paths, which are resolved against the directory of the glTF file.
+pub fn resolve(directory: &Path, uri: &str) -> anyhow::Result<Vec<u8>> {
+    if let Some(data) = uri.strip_prefix("data:") {
+        let (header, payload) = data
+            .split_once(',')
+            .ok_or_else(|| anyhow::anyhow!("data uri without payload"))?;
+
+        return if header.ends_with(";base64") {
+            base64::decode(payload).context("invalid base64 data uri")
+        } else {
+            Ok(percent_decode_str(payload).collect())
+        };
+    }
+
+    anyhow::ensure!(!uri.contains("://"), "unsupported uri scheme `{}`", uri);
+
+    let path = percent_decode_str(uri)
+        .decode_utf8()
+        .with_context(|| format!("invalid uri `{}`", uri))?;
+    let path = directory.join(&*path);
+    fs::read(&path).with_context(|| format!("failed to read `{}`", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base64_data() {
+        let data = resolve(
+            Path::new(""),
+            "data:application/octet-stream;base64,AQIDBA==",
+        );
+        assert_eq!(data.unwrap(), [1, 2, 3, 4]);
+
+        let err = resolve(Path::new(""), "data:;base64,AQ?D").unwrap_err();
+        assert_eq!(err.to_string(), "invalid base64 data uri");
+    }
+
+    #[test]
+    fn percent_encoded_data() {
+        let data = resolve(Path::new(""), "data:text/plain,a%20b%2C%00c");
+        assert_eq!(data.unwrap(), b"a b,\0c");
+
+        let err = resolve(Path::new(""), "data:text/plain").unwrap_err();
+        assert_eq!(err.to_string(), "data uri without payload");
+    }
+
+    #[test]
+    fn percent_encoded_path() {
+        let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+        let data = resolve(&directory, "mirrored%5Fuv.gltf").unwrap();
+        assert_eq!(data, fs::read(directory.join("mirrored_uv.gltf")).unwrap());
+
+        let err = resolve(&directory, "%FF.bin").unwrap_err();
+        assert_eq!(err.to_string(), "invalid uri `%FF.bin`");
+    }
+
+    #[test]
+    fn unsupported_scheme() {
+        let err = resolve(Path::new(""), "https://example.com/a.bin").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "unsupported uri scheme `https://example.com/a.bin`"
+        );
+    }
+}